@@ -0,0 +1,112 @@
+//! Benchmarks of movement phase adjudication on large synthetic maps.
+
+use std::collections::{HashMap, HashSet};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use radip::{
+    adjudicate_compiled,
+    base::{Hold, Move, SupportHold, SupportMove},
+    CompiledMap, Engine, Map, MapState, Order, Orders, Province, RuleSet, Unit,
+};
+
+fn prov_name(x: usize, y: usize) -> String {
+    format!("p{}_{}", x, y)
+}
+
+/// A square grid of land provinces, each adjacent to its horizontal and vertical neighbours.
+fn grid_map(size: usize) -> Map {
+    let mut provinces = HashMap::new();
+    let mut army_adj = HashSet::new();
+    for x in 0..size {
+        for y in 0..size {
+            provinces.insert(prov_name(x, y), Province { coasts: HashSet::new(), is_sea: false, canal: None, impassable: false });
+            if x + 1 < size {
+                army_adj.insert((prov_name(x, y), prov_name(x + 1, y)));
+                army_adj.insert((prov_name(x + 1, y), prov_name(x, y)));
+            }
+            if y + 1 < size {
+                army_adj.insert((prov_name(x, y), prov_name(x, y + 1)));
+                army_adj.insert((prov_name(x, y + 1), prov_name(x, y)));
+            }
+        }
+    }
+    Map { provinces, fleet_adj: HashSet::new(), army_adj, one_way: HashSet::new() }
+}
+
+/// Armies of 32 powers on half of the provinces of the grid,
+/// with a random mix of holds, moves and supports.
+fn grid_position(map: &Map, rng: &mut StdRng) -> (MapState, Orders) {
+    let mut provs = map.provinces.keys().cloned().collect::<Vec<_>>();
+    provs.sort();
+    provs.shuffle(rng);
+    provs.truncate(provs.len() / 2);
+
+    let mut state = MapState { units: HashMap::new(), ownership: HashMap::new() };
+    for prov in provs.iter() {
+        state.units.insert(prov.clone(), Unit::Army(format!("power{}", rng.gen_range(0..32))));
+    }
+
+    let neighbours = |prov: &str| {
+        let mut adj = map.army_adj.iter().filter(|(a, _)| a == prov).map(|(_, b)| b.clone()).collect::<Vec<_>>();
+        adj.sort();
+        adj
+    };
+
+    let mut moves = HashMap::new();
+    for prov in provs.iter() {
+        if rng.gen_bool(0.5) {
+            moves.insert(prov.clone(), neighbours(prov).choose(rng).unwrap().clone());
+        }
+    }
+
+    let mut orders = Orders::new();
+    for prov in provs.iter() {
+        let order: Box<dyn Order> = if let Some(dest) = moves.get(prov) {
+            Box::new(Move { dest: (dest.clone(), "".to_string()), via_convoy: false })
+        } else {
+            let adj = neighbours(prov);
+            let supportable = provs.iter()
+                .filter(|other| moves.get(*other).map(|dest| adj.contains(dest)).unwrap_or(false))
+                .filter(|other| adj.contains(other) || rng.gen_bool(0.1))
+                .collect::<Vec<_>>();
+            let holding = adj.iter().filter(|a| state.units.contains_key(*a) && !moves.contains_key(*a)).collect::<Vec<_>>();
+
+            match rng.gen_range(0..3) {
+                0 if !supportable.is_empty() => {
+                    let src = supportable.choose(rng).unwrap();
+                    Box::new(SupportMove { src: src.to_string(), dest: moves[*src].clone() })
+                }
+                1 if !holding.is_empty() => Box::new(SupportHold { target: holding.choose(rng).unwrap().to_string() }),
+                _ => Box::new(Hold),
+            }
+        };
+        orders.insert(prov.clone(), order);
+    }
+
+    (state, orders)
+}
+
+fn bench_grid(c: &mut Criterion) {
+    let rules = RuleSet::default();
+    let mut group = c.benchmark_group("grid");
+    group.sample_size(10);
+
+    for size in [10, 20, 30] {
+        let map = grid_map(size);
+        let mut rng = StdRng::seed_from_u64(size as u64);
+        let (state, orders) = grid_position(&map, &mut rng);
+        let map = CompiledMap::new(&map);
+
+        for engine in [Engine::Iterative, Engine::GuessBacktrack] {
+            group.bench_with_input(BenchmarkId::new(format!("{:?}", engine), state.units.len()), &size, |b, _| {
+                b.iter(|| adjudicate_compiled(&map, &rules, &state, &orders, engine).unwrap())
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_grid);
+criterion_main!(benches);
@@ -0,0 +1,213 @@
+//! Adjudication of adjustment (build) phases.
+//!
+//! Builds follow the standard rules: a power may build in its unoccupied, owned
+//! home supply centers, and must disband units if it has more units than supply centers.
+//! Powers that do not disband enough units have units removed in civil disorder.
+
+use std::{collections::HashMap, error::Error, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    utils::{count_supply, count_units, disband_cd, MapMeta},
+    Map, MapState, Province, ProvinceAbbr, Unit,
+};
+
+/// An adjustment order, given in a province.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "coast", rename_all = "snake_case")]
+pub enum Build {
+    Disband,
+    Fleet(String),
+    Army,
+    /// Explicitly waive a build.
+    Waive,
+}
+
+impl Build {
+    /// Whether this order uses up one of a power's builds.
+    pub fn is_build(&self) -> bool {
+        !matches!(self, Build::Disband)
+    }
+}
+
+/// Map of adjustment orders, keyed by province.
+pub type Builds = HashMap<ProvinceAbbr, Build>;
+
+/// Reason an adjustment order is illegal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BuildError {
+    UnknownProvince(String),
+    /// The power has no builds this phase.
+    NoBuilds(String),
+    /// The power has no disbands this phase.
+    NoDisbands(String),
+    /// The power issued more orders than it has adjustments.
+    TooMany { power: String, allowed: usize },
+    NotHomeCenter(String),
+    NotOwned(String),
+    Occupied(String),
+    NoUnit(String),
+    /// The order is for a province or unit of another power.
+    NotYours(String),
+    /// A fleet can't be built in a province without a coast.
+    Landlocked(String),
+    /// An army can't be built in a sea province.
+    AtSea(String),
+    /// The province has no such coast, or a coast must be given because it has several.
+    InvalidCoast(String, String),
+}
+
+impl Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::UnknownProvince(prov) => write!(f, "Unknown province {}", prov),
+            BuildError::NoBuilds(power) => write!(f, "{} has no builds", power),
+            BuildError::NoDisbands(power) => write!(f, "{} has no disbands", power),
+            BuildError::TooMany { power, allowed } => write!(f, "{} only has {} adjustments", power, allowed),
+            BuildError::NotHomeCenter(prov) => write!(f, "Build location {} is not a home center", prov),
+            BuildError::NotOwned(prov) => write!(f, "Build location {} is not owned", prov),
+            BuildError::Occupied(prov) => write!(f, "Build location {} is occupied", prov),
+            BuildError::NoUnit(prov) => write!(f, "No unit to disband in {}", prov),
+            BuildError::NotYours(prov) => write!(f, "{} does not belong to you", prov),
+            BuildError::Landlocked(prov) => write!(f, "Can't build a fleet in landlocked {}", prov),
+            BuildError::AtSea(prov) => write!(f, "Can't build an army in {}", prov),
+            BuildError::InvalidCoast(prov, coast) if coast.is_empty() => write!(f, "A coast of {} must be given", prov),
+            BuildError::InvalidCoast(prov, coast) => write!(f, "{} has no coast {:?}", prov, coast),
+        }
+    }
+}
+
+impl Error for BuildError {}
+
+/// The coast a fleet built in `province` is placed on: the given coast,
+/// or the only coast of the province if none is given.
+/// Returns `None` if the province has no such coast, or several coasts and none was given.
+fn build_coast<'a>(province: &'a Province, coast: &'a str) -> Option<&'a str> {
+    if province.coasts.contains(coast) || (coast.is_empty() && province.coasts.is_empty()) {
+        return Some(coast);
+    }
+    match (coast.is_empty(), province.coasts.len()) {
+        (true, 1) => province.coasts.iter().next().map(String::as_str),
+        _ => None,
+    }
+}
+
+/// Returns the number of builds (if positive) or disbands (if negative)
+/// the given power has.
+pub fn adjustment_count(state: &MapState, power: &str) -> isize {
+    count_supply(state, power) as isize - count_units(state, power) as isize
+}
+
+/// Validate a single adjustment order, returning the power it is given for.
+pub fn validate_build(map: &Map, meta: &MapMeta, state: &MapState, prov: &str, build: &Build) -> Result<String, BuildError> {
+    let province = map.provinces.get(prov).ok_or_else(|| BuildError::UnknownProvince(prov.to_string()))?;
+
+    if let Build::Disband = build {
+        let power = state.units.get(prov).map(Unit::nationality).ok_or_else(|| BuildError::NoUnit(prov.to_string()))?;
+        if adjustment_count(state, &power) >= 0 {
+            return Err(BuildError::NoDisbands(power));
+        }
+        return Ok(power);
+    }
+
+    let power = state.ownership.get(prov).ok_or_else(|| BuildError::NotOwned(prov.to_string()))?;
+    if meta.provinces.get(prov).map(|p| &p.home_sc) != Some(power) {
+        return Err(BuildError::NotHomeCenter(prov.to_string()));
+    }
+    if state.units.contains_key(prov) {
+        return Err(BuildError::Occupied(prov.to_string()));
+    }
+    if adjustment_count(state, power) <= 0 {
+        return Err(BuildError::NoBuilds(power.to_string()));
+    }
+
+    match build {
+        Build::Army => if province.is_sea {
+            return Err(BuildError::AtSea(prov.to_string()));
+        },
+        Build::Fleet(coast) => {
+            if !map.fleet_adj.iter().any(|(src, _)| src.0 == prov) {
+                return Err(BuildError::Landlocked(prov.to_string()));
+            }
+            if build_coast(province, coast).is_none() {
+                return Err(BuildError::InvalidCoast(prov.to_string(), coast.to_string()));
+            }
+        },
+        Build::Waive | Build::Disband => {},
+    }
+
+    Ok(power.to_string())
+}
+
+/// Validate the complete set of adjustment orders of a power.
+pub fn validate_builds(map: &Map, meta: &MapMeta, state: &MapState, power: &str, builds: &Builds) -> Result<(), BuildError> {
+    let mut sorted = builds.iter().collect::<Vec<_>>();
+    sorted.sort_by_key(|(prov, _)| *prov);
+    for (prov, build) in sorted {
+        if validate_build(map, meta, state, prov, build)? != power {
+            return Err(BuildError::NotYours(prov.to_string()));
+        }
+    }
+
+    let allowed = adjustment_count(state, power).unsigned_abs();
+    if builds.len() > allowed {
+        return Err(BuildError::TooMany { power: power.to_string(), allowed });
+    }
+
+    Ok(())
+}
+
+/// Adjudicate an adjustment phase.
+///
+/// Illegal orders are ignored. If a power issues more orders than it has adjustments,
+/// the orders in the alphabetically first provinces are used.
+/// Powers that do not disband enough units have units disbanded using [`disband_cd`].
+///
+/// Returns the new board and the adjustments that were carried out, including civil disorder disbands
+/// and the coasts of fleets built without one.
+pub fn adjudicate_adjustments(map: &Map, meta: &MapMeta, state: &MapState, builds: &Builds) -> (MapState, Builds) {
+    let mut by_power: HashMap<String, Vec<(&ProvinceAbbr, &Build)>> = HashMap::new();
+    for (prov, build) in builds.iter() {
+        if let Ok(power) = validate_build(map, meta, state, prov, build) {
+            by_power.entry(power).or_default().push((prov, build));
+        }
+    }
+
+    let mut new_state = state.clone();
+    let mut applied = Builds::new();
+    for (power, mut orders) in by_power {
+        orders.sort_by(|a, b| a.0.cmp(b.0));
+        orders.truncate(adjustment_count(state, &power).unsigned_abs());
+
+        for (prov, build) in orders {
+            let build = match build {
+                Build::Fleet(coast) => Build::Fleet(build_coast(&map.provinces[prov], coast).unwrap().to_string()),
+                build => build.clone(),
+            };
+            match &build {
+                Build::Army => { new_state.units.insert(prov.to_string(), Unit::Army(power.to_string())); },
+                Build::Fleet(coast) => { new_state.units.insert(prov.to_string(), Unit::Fleet(power.to_string(), coast.to_string())); },
+                Build::Disband => { new_state.units.remove(prov); },
+                Build::Waive => {},
+            }
+            applied.insert(prov.to_string(), build);
+        }
+    }
+
+    // civil disorder
+    let mut powers = meta.powers.keys().collect::<Vec<_>>();
+    powers.sort();
+    for power in powers {
+        while count_units(&new_state, power) > count_supply(&new_state, power) {
+            let prov = match disband_cd(map, meta, &new_state, power) {
+                Some(prov) => prov,
+                None => break,
+            };
+            new_state.units.remove(&prov);
+            applied.insert(prov, Build::Disband);
+        }
+    }
+
+    (new_state, applied)
+}
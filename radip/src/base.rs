@@ -4,7 +4,7 @@
 
 #![allow(unused_imports)]
 
-use crate::{FleetLoc, Map, MapState, Order, Orders, Province, ProvinceAbbr, Unit};
+use crate::{rules::ConvoyIntent, CompiledMap, FleetLoc, IndexedOrders, Map, MapState, Order, Orders, Province, ProvinceAbbr, RuleSet, Unit};
 use serde::{Deserialize, Serialize};
 use std::{
     collections::{HashMap, HashSet},
@@ -16,37 +16,27 @@ use std::{
 pub struct Hold;
 
 pub fn deps_for_hold(
-    map: &crate::Map,
+    map: &CompiledMap,
     state: &crate::MapState,
-    orders: &crate::Orders,
+    orders: &IndexedOrders,
     this_prov: &str,
 ) -> HashSet<String> {
-    let mut deps = HashSet::new();
-    for (src2, order2) in orders {
-        if let Some(mov) = order2.downcast_ref::<Move>() {
-            if mov.dest.0 == this_prov {
-                deps.insert(src2.to_string());
-            }
-        }
-    }
-    deps
+    orders.moves_to(this_prov).iter().map(|src2| src2.to_string()).collect()
 }
 
 pub fn is_dislodged(
-    map: &crate::Map,
+    map: &CompiledMap,
     state: &crate::MapState,
-    orders: &crate::Orders,
+    orders: &IndexedOrders,
     this_prov: &str,
     order_status: &std::collections::HashMap<String, bool>,
 ) -> Option<bool> {
     let mut possible_dislodge = false;
-    for (src2, order2) in orders {
-        if Move::is_move_to(order2.deref(), this_prov) {
-            match order_status.get(src2) {
-                Some(true) => return Some(false),
-                Some(false) => {}
-                None => possible_dislodge = true,
-            }
+    for src2 in orders.moves_to(this_prov) {
+        match order_status.get(*src2) {
+            Some(true) => return Some(false),
+            Some(false) => {}
+            None => possible_dislodge = true,
         }
     }
 
@@ -61,9 +51,10 @@ pub fn is_dislodged(
 impl Order for Hold {
     fn deps(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        _rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String> {
         deps_for_hold(map, state, orders, this_prov)
@@ -71,9 +62,10 @@ impl Order for Hold {
 
     fn adjudicate(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        _rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &std::collections::HashMap<String, bool>,
     ) -> Option<bool> {
@@ -95,6 +87,10 @@ impl Order for Hold {
 #[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
 pub struct Move {
     pub dest: FleetLoc,
+
+    /// Whether the army is ordered to move by convoy, even if it is adjacent to its destination.
+    #[serde(default)]
+    pub via_convoy: bool,
 }
 
 impl Move {
@@ -112,9 +108,10 @@ impl Move {
 
 /// Compute whether the given order is in a head-to-head battle.
 pub fn is_head_to_head(
-    map: &crate::Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &crate::MapState,
-    orders: &crate::Orders,
+    orders: &IndexedOrders,
     order_status: &std::collections::HashMap<String, bool>,
     src: &str,
 ) -> Option<bool> {
@@ -124,14 +121,20 @@ pub fn is_head_to_head(
         return Some(false)
     }
 
-    match (is_convoy_path(map, state, orders, order_status, src), is_convoy_path(map, state, orders, order_status, dest_prov)) {
+    // armies ordered via convoy never move over the border between the provinces
+    let via_convoy = |prov: &str| orders[prov].downcast_ref::<Move>().is_some_and(|mov| mov.via_convoy);
+    if via_convoy(src) || via_convoy(dest_prov) {
+        return Some(false);
+    }
+
+    match (is_convoy_path(map, rules, state, orders, order_status, src), is_convoy_path(map, rules, state, orders, order_status, dest_prov)) {
         (Some(true), _) | (_, Some(true)) => Some(false),
         (None, _) | (_, None) => None,
         (Some(false), Some(false)) => Some(true)
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Bounds {
     pub min: u32,
     pub max: u32,
@@ -141,9 +144,10 @@ pub struct Bounds {
 impl Order for Move {
     fn deps(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        _rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String> {
         if this_prov == self.dest.0 { // check is required for convoys!!
@@ -151,45 +155,23 @@ impl Order for Move {
         }
 
         let mut deps = HashSet::new();
-        for (src2, order2) in orders {
-            if let Some(sup) = order2.downcast_ref::<SupportMove>() {
-                if sup.dest == self.dest.0
-                    && orders.contains_key(&sup.src)
-                    && Move::is_move_to(orders[&sup.src].deref(), &sup.dest)
-                {
-                    deps.insert(src2.to_string());
-                }
-            }
-            if let Some(sup) = order2.downcast_ref::<Convoy>() {
-                if (sup.dest == self.dest.0 || sup.src == self.dest.0) // second clause required to compute whether self.dest is part of head-to-head battle
-                    && orders.contains_key(&sup.src)
-                    && Move::is_move_to(orders[&sup.src].deref(), &sup.dest)
-                {
-                    deps.insert(src2.to_string());
-                }
-            }
-            if let Some(sup) = order2.downcast_ref::<SupportHold>() {
-                if sup.target == self.dest.0
-                    && orders.contains_key(&sup.target)
-                    && !orders[&sup.target].is::<Move>()
-                {
-                    deps.insert(src2.to_string());
-                }
-            }
+        for src2 in orders.moves_to(&self.dest.0) {
+            // supports and convoys of moves to the same destination
+            deps.extend(orders.support_moves(src2, &self.dest.0).iter().map(|prov| prov.to_string()));
+            deps.extend(orders.convoys(src2, &self.dest.0).iter().map(|prov| prov.to_string()));
+        }
+        if orders.contains_key(&self.dest.0) && !orders[&self.dest.0].is::<Move>() {
+            deps.extend(orders.support_holds(&self.dest.0).iter().map(|prov| prov.to_string()));
         }
 
-        if orders.contains_key(&self.dest.0) && orders[&self.dest.0].is::<Move>() {
+        if let Some(dest2) = orders.move_dest(&self.dest.0) {
             deps.insert(self.dest.0.clone());
+            // required to compute whether self.dest is part of head-to-head battle
+            deps.extend(orders.convoys(&self.dest.0, dest2).iter().map(|prov| prov.to_string()));
 
-            if Move::is_move_to(orders[&self.dest.0].deref(), this_prov) {
+            if dest2 == this_prov {
                 // possible head-to-head
-                for (src2, order2) in orders {
-                    if let Some(sup) = order2.downcast_ref::<SupportMove>() {
-                        if sup.src == self.dest.0 && sup.dest == this_prov {
-                            deps.insert(src2.to_string());
-                        }
-                    }
-                }
+                deps.extend(orders.support_moves(&self.dest.0, this_prov).iter().map(|prov| prov.to_string()));
             }
         }
         deps
@@ -197,9 +179,10 @@ impl Order for Move {
 
     fn adjudicate(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &std::collections::HashMap<String, bool>,
     ) -> Option<bool> {
@@ -207,22 +190,22 @@ impl Order for Move {
             return Some(false)
         }
 
-        let attack_strength = compute_attack_strength(map, state, orders, order_status, this_prov);
+        let attack_strength = compute_attack_strength(map, rules, state, orders, order_status, this_prov);
 
         let mut strengths: Vec<Bounds> = Vec::new();
         if orders.contains_key(&self.dest.0)
             && Move::is_move_to(orders[&self.dest.0].deref(), this_prov)
         {
-            let is_hth = is_head_to_head(map, state, orders, order_status, this_prov);
+            let is_hth = is_head_to_head(map, rules, state, orders, order_status, this_prov);
             match is_hth {
                 Some(false) => {},
                 None => {
-                    let mut def = compute_defend_strength(map, state, orders, order_status, self.dest.0.as_str());
+                    let mut def = compute_defend_strength(map, rules, state, orders, order_status, self.dest.0.as_str());
                     def.min = 0;
                     strengths.push(def);   
                 },
                 Some(true) => {
-                    let def = compute_defend_strength(map, state, orders, order_status, self.dest.0.as_str());
+                    let def = compute_defend_strength(map, rules, state, orders, order_status, self.dest.0.as_str());
                     strengths.push(def);
                 }
             }
@@ -245,23 +228,20 @@ impl Order for Move {
             // not head-to-head battle
         }
 
-        for (province2, order2) in orders.iter() {
-            if Move::is_move_to(order2.deref(), self.dest.0.as_str()) && province2 != this_prov {
+        for province2 in orders.moves_to(&self.dest.0) {
+            if *province2 != this_prov {
                 strengths.push(compute_prevent_strength(
                     map,
+                    rules,
                     state,
                     orders,
                     order_status,
-                    province2.as_str(),
+                    province2,
                 ));
             }
         }
 
         let mut beats_all_bounds = true;
-        println!(
-            "{} | attack: {:?} | defends: {:?}",
-            this_prov, attack_strength, strengths
-        );
         for strength in strengths.iter() {
             if attack_strength.max <= strength.min {
                 return Some(false);
@@ -295,55 +275,45 @@ pub struct SupportHold {
 }
 
 pub fn deps_for_tap(
-    map: &Map,
+    map: &CompiledMap,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     this_prov: &str,
 ) -> HashSet<String> {
     let mut deps = HashSet::new();
-    for (prov_it, order_it) in orders.iter() {
-        if Move::is_move_to(order_it.deref(), this_prov) {
-            deps.insert(prov_it.to_string());
-        }
-        if let Some(sup) = order_it.downcast_ref::<Convoy>() {
-            if sup.dest == this_prov
-                && orders.contains_key(&sup.src)
-                && Move::is_move_to(orders[&sup.src].deref(), &sup.dest)
-            {
-                deps.insert(prov_it.to_string());
-            }
-        }
+    for prov_it in orders.moves_to(this_prov) {
+        deps.insert(prov_it.to_string());
+        deps.extend(orders.convoys(prov_it, this_prov).iter().map(|prov| prov.to_string()));
     }
     deps
 }
 
 pub fn is_untapped(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     this_prov: &str,
     exception: &str,
 ) -> Option<bool> {
     let mut possibly_tapped = false;
-    for (prov_it, order_it) in orders.iter() {
-        if Move::is_move_to(order_it.deref(), this_prov) {
-            if state.units[prov_it].nationality() == state.units[this_prov].nationality() {
-                continue;
-            }
+    for prov_it in orders.moves_to(this_prov) {
+        if state.units[*prov_it].nationality() == state.units[this_prov].nationality() {
+            continue;
+        }
 
-            if prov_it == exception {
-                match order_status.get(prov_it).map(|x| *x) {
-                    Some(true) => return Some(false),
-                    None => possibly_tapped = true,
-                    Some(false) => {}
-                }
-            } else {
-                match is_path(map, state, orders, order_status, prov_it) {
-                    Some(true) => return Some(false),
-                    None => possibly_tapped = true,
-                    Some(false) => {}
-                }
+        if *prov_it == exception {
+            match order_status.get(*prov_it).map(|x| *x) {
+                Some(true) => return Some(false),
+                None => possibly_tapped = true,
+                Some(false) => {}
+            }
+        } else {
+            match is_path(map, rules, state, orders, order_status, prov_it) {
+                Some(true) => return Some(false),
+                None => possibly_tapped = true,
+                Some(false) => {}
             }
         }
     }
@@ -359,9 +329,10 @@ pub fn is_untapped(
 impl Order for SupportHold {
     fn deps(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        _rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String> {
         if !orders.contains_key(&self.target)
@@ -376,9 +347,10 @@ impl Order for SupportHold {
 
     fn adjudicate(
         &self,
-        map: &Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &MapState,
-        orders: &Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &HashMap<String, bool>,
     ) -> Option<bool> {
@@ -388,7 +360,7 @@ impl Order for SupportHold {
         {
             Some(false)
         } else {
-            is_untapped(map, state, orders, order_status, this_prov, "")
+            is_untapped(map, rules, state, orders, order_status, this_prov, "")
         }
     }
 
@@ -416,15 +388,33 @@ impl SupportMove {
             .map(|s| s.src == src && s.dest == dest)
             .unwrap_or(false)
     }
+
+    /// Whether the supporting unit can reach the destination of the supported move.
+    fn can_reach_dest(&self, map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, this_prov: &str) -> bool {
+        if !unit_can_reach(map, state, this_prov, &self.dest) {
+            return false;
+        }
+
+        let dest_coast = orders.get(&self.src)
+            .and_then(|order| order.downcast_ref::<Move>())
+            .map(|mov| match &state.units[&self.src] {
+                Unit::Fleet(_, src_coast) => infer_coast(map, &self.src, src_coast, &mov.dest),
+                Unit::Army(_) => mov.dest.1.as_str(),
+            })
+            .unwrap_or("");
+        match &state.units[this_prov] {
+            Unit::Fleet(_, coast) if rules.support_requires_coast && !dest_coast.is_empty() => {
+                map.fleet_adjacent(this_prov, coast, &self.dest, dest_coast)
+            }
+            _ => true,
+        }
+    }
 }
 
-fn unit_can_reach(map: &Map, state: &MapState, src: &str, dest: &str) -> bool {
+pub(crate) fn unit_can_reach(map: &CompiledMap, state: &MapState, src: &str, dest: &str) -> bool {
     match &state.units[src] {
-        Unit::Army(_) => map.army_adj.contains(&(src.to_string(), dest.to_string())),
-        Unit::Fleet(_, coast) => map.fleet_adj.contains(&(
-            (src.to_string(), coast.to_string()),
-            (dest.to_string(), "".to_string()),
-        )),
+        Unit::Army(_) => map.army_adjacent(src, dest),
+        Unit::Fleet(_, coast) => map.fleet_adjacent(src, coast, dest, ""),
     }
 }
 
@@ -432,14 +422,15 @@ fn unit_can_reach(map: &Map, state: &MapState, src: &str, dest: &str) -> bool {
 impl Order for SupportMove {
     fn deps(
         &self,
-        map: &Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &MapState,
-        orders: &Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String> {
         if !orders.contains_key(&self.src)
             || !Move::is_move_to(orders[&self.src].deref(), &self.dest)
-            || !unit_can_reach(map, state, this_prov, &self.dest)
+            || !self.can_reach_dest(map, rules, state, orders, this_prov)
             || self.src == self.dest
         {
             HashSet::new()
@@ -450,9 +441,10 @@ impl Order for SupportMove {
 
     fn adjudicate(
         &self,
-        map: &Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &MapState,
-        orders: &Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &HashMap<String, bool>,
     ) -> Option<bool> {
@@ -460,12 +452,12 @@ impl Order for SupportMove {
 
         if !orders.contains_key(&self.src)
             || !Move::is_move_to(orders[&self.src].deref(), &self.dest)
-            || !unit_can_reach(map, state, this_prov, &self.dest)
+            || !self.can_reach_dest(map, rules, state, orders, this_prov)
             || self.src == self.dest
         {
             Some(false)
         } else {
-            is_untapped(map, state, orders, order_status, this_prov, &self.dest)
+            is_untapped(map, rules, state, orders, order_status, this_prov, &self.dest)
         }
     }
 
@@ -478,7 +470,7 @@ impl Order for SupportMove {
 /// 
 /// Succeeds iff the convoying unit
 /// * is a fleet
-/// * in a sea tile
+/// * in a sea tile (or a coastal tile, if [`RuleSet::coastal_convoys`] is set)
 /// * and is not dislodged.
 #[derive(PartialEq, Clone, Serialize, Deserialize, Debug)]
 pub struct Convoy {
@@ -490,12 +482,13 @@ pub struct Convoy {
 impl Order for Convoy {
     fn deps(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String> {
-        if !map.provinces[this_prov].is_sea {
+        if !map.provinces.get(this_prov).map(|p| p.is_sea || rules.coastal_convoys).unwrap_or(false) {
             return HashSet::new()
         }
         deps_for_hold(map, state, orders, this_prov)
@@ -503,13 +496,14 @@ impl Order for Convoy {
 
     fn adjudicate(
         &self,
-        map: &crate::Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &crate::MapState,
-        orders: &crate::Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &std::collections::HashMap<String, bool>,
     ) -> Option<bool> {
-        if !map.provinces[this_prov].is_sea {
+        if !map.provinces.get(this_prov).map(|p| p.is_sea || rules.coastal_convoys).unwrap_or(false) {
             return Some(false)
         }
         is_dislodged(map, state, orders, this_prov, order_status)
@@ -527,13 +521,14 @@ impl Order for Convoy {
 /// If the path of the move order is not successful, then the defend strength is 0.  
 /// Otherwise, the defend strength is 1 + the number of successful support orders.
 pub fn compute_defend_strength(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     src: &str,
 ) -> Bounds {
-    let possibly_no_path = match is_path(map, state, orders, order_status, src) {
+    let possibly_no_path = match is_path(map, rules, state, orders, order_status, src) {
         Some(false) => return Bounds { min: 0, max: 0 },
         Some(true) => false,
         None => true,
@@ -545,18 +540,16 @@ pub fn compute_defend_strength(
         .dest;
 
     let mut bounds = Bounds { min: 1, max: 1 };
-    for (province2, order2) in orders.iter() {
-        if SupportMove::is_support_to(order2.deref(), &src, &dest_prov) {
-            match order_status.get(province2) {
-                Some(true) => {
-                    bounds.min += 1;
-                    bounds.max += 1;
-                }
-                Some(false) => {}
-                None => {
-//                    println!("possible support for {}: {}", src, province2);
-                    bounds.max += 1;
-                }
+    for province2 in orders.support_moves(src, dest_prov) {
+        match order_status.get(*province2) {
+            Some(true) => {
+                bounds.min += 1;
+                bounds.max += 1;
+            }
+            Some(false) => {}
+            None => {
+//                println!("possible support for {}: {}", src, province2);
+                bounds.max += 1;
             }
         }
     }
@@ -574,20 +567,21 @@ pub fn compute_defend_strength(
 /// If the path of the move order is not successful, then the defend strength is 0.  
 /// Otherwise, the defend strength is 1 + the number of successful support orders.
 pub fn compute_prevent_strength(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     src: &str,
 ) -> Bounds {
-    let mut bounds = compute_defend_strength(map, state, orders, order_status, src);
+    let mut bounds = compute_defend_strength(map, rules, state, orders, order_status, src);
     
     let (dest_prov, dest_coast) = &orders[src]
         .downcast_ref::<Move>()
         .expect("compute_defend_strength computed without move order")
         .dest;
 
-    match is_head_to_head(map, state, orders, order_status, src) {
+    match is_head_to_head(map, rules, state, orders, order_status, src) {
         Some(false) => {},
         None => {
             if order_status.get(dest_prov).map(|x: &bool| *x) != Some(false) {
@@ -609,13 +603,14 @@ pub fn compute_prevent_strength(
 
 /// Compute the [attack strength](https://webdiplomacy.net/doc/DATC_v3_0.html#5.B.8) of the given move order.
 pub fn compute_attack_strength(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     src: &str,
 ) -> Bounds {
-    let possibly_no_path = match is_path(map, state, orders, order_status, src) {
+    let possibly_no_path = match is_path(map, rules, state, orders, order_status, src) {
         Some(false) => return Bounds { min: 0, max: 0 },
         Some(true) => false,
         None => true,
@@ -652,16 +647,8 @@ pub fn compute_attack_strength(
     }
 
     let mut bounds = Bounds { min: 1, max: 1 };
-    for (prov_it, order_it) in orders.iter() {
-        let support = match order_it.downcast_ref::<SupportMove>() {
-            Some(s) => s,
-            None => continue,
-        };
-
-        if !(support.src == src && support.dest == *dest_prov) {
-            continue;
-        }
-
+    for prov_it in orders.support_moves(src, dest_prov) {
+        let prov_it = *prov_it;
         match order_status.get(prov_it) {
             Some(true) => {
                 // if the destination nationality is definitely not the same as the supporter nationality, increase min + max by 1
@@ -697,9 +684,9 @@ pub fn compute_attack_strength(
 
 /// Compute the [hold strength](https://webdiplomacy.net/doc/DATC_v3_0.html#5.B.5) of the target province.
 pub fn compute_hold_strength(
-    map: &Map,
+    map: &CompiledMap,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     target: &str,
 ) -> Bounds {
@@ -717,17 +704,8 @@ pub fn compute_hold_strength(
     }
 
     let mut bounds = Bounds { min: 1, max: 1 };
-    for (prov_it, order_it) in orders.iter() {
-        let support = match order_it.downcast_ref::<SupportHold>() {
-            Some(s) => s,
-            None => continue,
-        };
-
-        if support.target != target {
-            continue;
-        };
-
-        match order_status.get(prov_it) {
+    for prov_it in orders.support_holds(target) {
+        match order_status.get(*prov_it) {
             Some(true) => {
                 bounds.min += 1;
                 bounds.max += 1;
@@ -745,73 +723,91 @@ pub fn compute_hold_strength(
     bounds
 }
 
-pub fn is_direct_path(map: &Map, state: &MapState, orders: &Orders, src: &str) -> bool {
-    let (dest_prov, dest_coast) = &orders[src]
+/// The coast of `dest` meant by a fleet at `src` on `src_coast` ordered there.
+///
+/// If the order omits the coast of a province with coasts, and the fleet can reach exactly one of them,
+/// that coast is meant (DATC 4.B.2). Otherwise the coast is as given, so that an ambiguous order
+/// still has no coast (DATC 4.B.1).
+pub fn infer_coast<'a>(map: &'a CompiledMap, src: &str, src_coast: &str, dest: &'a FleetLoc) -> &'a str {
+    match map.provinces.get(&dest.0) {
+        Some(province) if dest.1.is_empty() && !province.coasts.is_empty() => {
+            let mut reachable = province.coasts.iter()
+                .filter(|coast| map.fleet_adjacent(src, src_coast, &dest.0, coast));
+            match (reachable.next(), reachable.next()) {
+                (Some(coast), None) => coast,
+                _ => &dest.1,
+            }
+        }
+        _ => &dest.1,
+    }
+}
+
+pub fn is_direct_path(map: &CompiledMap, state: &MapState, orders: &IndexedOrders, src: &str) -> bool {
+    let mov = orders[src]
         .downcast_ref::<Move>()
-        .expect("is_direct_path should have move order")
-        .dest;
+        .expect("is_direct_path should have move order");
+    if mov.via_convoy {
+        return false;
+    }
+    let dest_prov = &mov.dest.0;
 
     match state
         .units
         .get(src)
         .expect("unit does not exist in is_path")
     {
-        Unit::Army(_) => map
-            .army_adj
-            .contains(&(src.to_string(), dest_prov.to_string())),
+        Unit::Army(_) => map.army_adjacent(src, dest_prov),
         Unit::Fleet(_, src_coast) => {
-            if !(map.provinces[dest_prov].coasts.contains(dest_coast)
-                || (dest_coast == "" && map.provinces[dest_prov].coasts.is_empty()))
+            let dest_province = match map.provinces.get(dest_prov) {
+                Some(p) => p,
+                None => return false,
+            };
+            let dest_coast = infer_coast(map, src, src_coast, &mov.dest);
+            if !(dest_province.coasts.contains(dest_coast)
+                || (dest_coast == "" && dest_province.coasts.is_empty()))
             {
                 return false;
             }
-            map.fleet_adj.contains(&(
-                (src.to_string(), src_coast.to_string()),
-                (dest_prov.to_string(), dest_coast.to_string()),
-            ))
+            map.fleet_adjacent(src, src_coast, dest_prov, dest_coast)
         }
     }
 }
 
-fn is_path_along(map: &Map, src: &str, dest: &str, convoys: &[String]) -> bool {
+fn is_path_along(map: &CompiledMap, src: &str, dest: &str, convoys: &[&str]) -> bool {
+    let (src, dest) = match (map.loc_id(src, ""), map.loc_id(dest, "")) {
+        (Some(src), Some(dest)) => (src, dest),
+        _ => return false,
+    };
+    let convoys = convoys.iter()
+        .filter_map(|convoy| map.loc_id(convoy, ""))
+        .collect::<HashSet<_>>();
+
     let mut visited = HashSet::new();
-    let mut stack = vec![src.to_string()];
-//    println!("-- convoy {} --", src);
+    let mut stack = vec![src];
     loop {
         let node = match stack.pop() {
             Some(n) => n,
             None => return false,
         };
-//        println!("node {}, {}",  &node, visited.len());
 
-        if map.fleet_adj.contains(&(
-            (node.to_string(), "".to_string()),
-            (dest.to_string(), "".to_string()),
-        )) && visited.len() != 0
-        {
+        let neighbours = map.convoy_neighbours(node);
+        if node != src && neighbours.binary_search(&dest).is_ok() {
             return true;
         }
 
-        for convoy in convoys.iter() {
-            if visited.contains(convoy) || node == *convoy {
-                continue;
-            }
-            if map.fleet_adj.contains(&(
-                (node.to_string(), "".to_string()),
-                (convoy.to_string(), "".to_string()),
-            )) {
-                stack.push(convoy.to_string());
+        for convoy in neighbours {
+            if convoys.contains(convoy) && visited.insert(*convoy) {
+                stack.push(*convoy);
             }
         }
-
-        visited.insert(node);
     }
 }
 
 pub fn is_convoy_path(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     src: &str,
 ) -> Option<bool> {
@@ -820,38 +816,45 @@ pub fn is_convoy_path(
         .expect("is_convoy_path should have move order")
         .dest;
 
-    match state
+    let natl = match state
         .units
         .get(src)
         .expect("unit does not exist in is_path")
     {
         Unit::Fleet(_, _) => return Some(false),
-        _ => {}
-    }
+        Unit::Army(natl) => natl,
+    };
 
-    if map.provinces[dest_prov].is_sea {
+    if map.provinces.get(dest_prov).map(|p| p.is_sea).unwrap_or(true) {
         return Some(false);
     }
 
-    let mut possible_convoys = Vec::new();
-    let mut definite_convoys = Vec::new();
-    for (prov_it, order_it) in orders.iter() {
-        let convoy = match order_it.downcast_ref::<Convoy>() {
-            Some(c) => c,
-            None => continue,
-        };
-
-        if !(convoy.src == src && convoy.dest == *dest_prov) {
-            continue;
+    // armies adjacent to their destination might not intend to be convoyed,
+    // unless they were ordered via convoy
+    if is_direct_path(map, state, orders, src) {
+        match rules.convoy_intent {
+            ConvoyIntent::AnyRoute => {}
+            ConvoyIntent::Never => return Some(false),
+            ConvoyIntent::OwnFleet => {
+                let own_convoy = orders.convoys(src, dest_prov).iter()
+                    .any(|prov_it| state.units.get(*prov_it).map(Unit::nationality).as_ref() == Some(natl));
+                if !own_convoy {
+                    return Some(false);
+                }
+            }
         }
+    }
 
-        match order_status.get(prov_it) {
+    let mut possible_convoys = Vec::new();
+    let mut definite_convoys = Vec::new();
+    for prov_it in orders.convoys(src, dest_prov) {
+        match order_status.get(*prov_it) {
             Some(true) => {
-                possible_convoys.push(prov_it.to_string());
-                definite_convoys.push(prov_it.to_string());
+                possible_convoys.push(*prov_it);
+                definite_convoys.push(*prov_it);
             }
             None => {
-                possible_convoys.push(prov_it.to_string());
+                possible_convoys.push(*prov_it);
             }
             Some(false) => {}
         }
@@ -870,9 +873,10 @@ pub fn is_convoy_path(
 }
 
 pub fn is_path(
-    map: &Map,
+    map: &CompiledMap,
+    rules: &RuleSet,
     state: &MapState,
-    orders: &Orders,
+    orders: &IndexedOrders,
     order_status: &HashMap<String, bool>,
     src: &str,
 ) -> Option<bool> {
@@ -880,5 +884,5 @@ pub fn is_path(
         return Some(true);
     }
 
-    is_convoy_path(map, state, orders, order_status, src)
+    is_convoy_path(map, rules, state, orders, order_status, src)
 }
@@ -0,0 +1,196 @@
+//! Consistency checks for variant maps.
+//!
+//! The adjudicator assumes the map is well formed; for example, that adjacencies are symmetric
+//! except for one-way passages, and only refer to provinces and coasts on the map.
+//! [`Map::validate`] and [`MapMeta::validate`] list the ways in which a variant breaks these assumptions,
+//! so that broken variants can be rejected before a game is started.
+
+use std::{
+    collections::{BTreeSet, HashSet},
+    error::Error,
+    fmt::Display,
+};
+
+use crate::{utils::MapMeta, FleetLoc, Map};
+
+/// A problem with a variant.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum MapProblem {
+    /// An adjacency, canal or one-way passage refers to a province that is not on the map.
+    UnknownProvince(String),
+    /// An army can move from the first province to the second, but not back,
+    /// and the provinces are not a one-way passage.
+    AsymmetricArmyAdj(String, String),
+    /// A fleet can move from the first location to the second, but not back,
+    /// and the provinces are not a one-way passage.
+    AsymmetricFleetAdj(FleetLoc, FleetLoc),
+    /// A fleet adjacency or canal refers to a coast the province doesn't have.
+    UnknownCoast(String, String),
+    /// An army adjacency involves the given sea province.
+    SeaArmyAdj(String),
+    /// A sea province is a canal.
+    SeaCanal(String),
+    /// An adjacency or canal involves the given impassable province.
+    ImpassableAdj(String),
+    /// A one-way passage goes between provinces that are not adjacent in its direction.
+    OneWayNotAdjacent(String, String),
+    /// A province is not adjacent to anything.
+    Isolated(String),
+    /// A home supply center is not a supply center.
+    HomeNotSc(String),
+    /// A starting unit is in a province that is not on the map.
+    UnknownStartingProvince(String),
+    /// A power has no starting units.
+    NoUnits(String),
+}
+
+impl Display for MapProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MapProblem::UnknownProvince(prov) => write!(f, "Adjacency refers to unknown province {}", prov),
+            MapProblem::AsymmetricArmyAdj(a, b) => write!(f, "Army adjacency from {} to {} has no reverse", a, b),
+            MapProblem::AsymmetricFleetAdj(a, b) => write!(f, "Fleet adjacency from {} to {} has no reverse", fmt_loc(a), fmt_loc(b)),
+            MapProblem::UnknownCoast(prov, coast) => write!(f, "Fleet adjacency or canal refers to unknown coast {} of {}", coast, prov),
+            MapProblem::SeaArmyAdj(prov) => write!(f, "Sea province {} has army adjacencies", prov),
+            MapProblem::SeaCanal(prov) => write!(f, "Sea province {} is a canal", prov),
+            MapProblem::ImpassableAdj(prov) => write!(f, "Impassable province {} has adjacencies", prov),
+            MapProblem::OneWayNotAdjacent(a, b) => write!(f, "One-way passage from {} to {} has no adjacency", a, b),
+            MapProblem::Isolated(prov) => write!(f, "Province {} has no adjacencies", prov),
+            MapProblem::HomeNotSc(prov) => write!(f, "Home supply center {} is not a supply center", prov),
+            MapProblem::UnknownStartingProvince(prov) => write!(f, "Starting unit in unknown province {}", prov),
+            MapProblem::NoUnits(power) => write!(f, "Power {} has no starting units", power),
+        }
+    }
+}
+
+impl Error for MapProblem {}
+
+fn fmt_loc((prov, coast): &FleetLoc) -> String {
+    if coast.is_empty() {
+        prov.to_string()
+    } else {
+        format!("{} ({})", prov, coast)
+    }
+}
+
+impl Map {
+    /// List the problems with this map, in a deterministic order.
+    /// A well formed map has none.
+    pub fn validate(&self) -> Vec<MapProblem> {
+        let mut problems = BTreeSet::new();
+        let mut connected = HashSet::new();
+        let is_one_way = |a: &str, b: &str| self.one_way.contains(&(a.to_string(), b.to_string()));
+
+        for (a, b) in self.army_adj.iter() {
+            connected.insert(a);
+            connected.insert(b);
+            for prov in [a, b] {
+                match self.provinces.get(prov) {
+                    None => { problems.insert(MapProblem::UnknownProvince(prov.to_string())); }
+                    Some(province) if province.is_sea => { problems.insert(MapProblem::SeaArmyAdj(prov.to_string())); }
+                    Some(province) if province.impassable => { problems.insert(MapProblem::ImpassableAdj(prov.to_string())); }
+                    Some(_) => {}
+                }
+            }
+            if !self.army_adj.contains(&(b.to_string(), a.to_string())) && !is_one_way(a, b) {
+                problems.insert(MapProblem::AsymmetricArmyAdj(a.to_string(), b.to_string()));
+            }
+        }
+
+        let check_loc = |problems: &mut BTreeSet<MapProblem>, (prov, coast): &FleetLoc| {
+            match self.provinces.get(prov) {
+                None => { problems.insert(MapProblem::UnknownProvince(prov.to_string())); }
+                Some(province) if !coast.is_empty() && !province.coasts.contains(coast) => {
+                    problems.insert(MapProblem::UnknownCoast(prov.to_string(), coast.to_string()));
+                }
+                Some(province) if province.impassable => {
+                    problems.insert(MapProblem::ImpassableAdj(prov.to_string()));
+                }
+                Some(_) => {}
+            }
+        };
+
+        for (a, b) in self.fleet_adj.iter() {
+            connected.insert(&a.0);
+            connected.insert(&b.0);
+            check_loc(&mut problems, a);
+            check_loc(&mut problems, b);
+            if !self.fleet_adj.contains(&(b.clone(), a.clone())) && !is_one_way(&a.0, &b.0) {
+                problems.insert(MapProblem::AsymmetricFleetAdj(a.clone(), b.clone()));
+            }
+        }
+
+        for (prov, province) in self.provinces.iter() {
+            let canal = match &province.canal {
+                Some(canal) => canal,
+                None => continue,
+            };
+            if province.is_sea {
+                problems.insert(MapProblem::SeaCanal(prov.to_string()));
+            }
+            if province.impassable {
+                problems.insert(MapProblem::ImpassableAdj(prov.to_string()));
+            }
+            for (a, b) in canal.connects.iter() {
+                check_loc(&mut problems, a);
+                check_loc(&mut problems, b);
+            }
+        }
+
+        for (a, b) in self.one_way.iter() {
+            let mut known = true;
+            for prov in [a, b] {
+                if !self.provinces.contains_key(prov) {
+                    problems.insert(MapProblem::UnknownProvince(prov.to_string()));
+                    known = false;
+                }
+            }
+            let adjacent = self.army_adj.contains(&(a.to_string(), b.to_string()))
+                || self.fleet_adj.iter().any(|(src, dest)| src.0 == *a && dest.0 == *b)
+                || self.provinces.values().filter_map(|p| p.canal.as_ref())
+                    .flat_map(|canal| canal.connects.iter())
+                    .any(|(x, y)| (x.0 == *a && y.0 == *b) || (x.0 == *b && y.0 == *a));
+            if known && !adjacent {
+                problems.insert(MapProblem::OneWayNotAdjacent(a.to_string(), b.to_string()));
+            }
+        }
+
+        for (prov, province) in self.provinces.iter() {
+            if !connected.contains(prov) && !province.impassable {
+                problems.insert(MapProblem::Isolated(prov.to_string()));
+            }
+        }
+
+        problems.into_iter().collect()
+    }
+}
+
+impl MapMeta {
+    /// List the problems with this metadata for the given map, in a deterministic order.
+    pub fn validate(&self, map: &Map) -> Vec<MapProblem> {
+        let mut problems = BTreeSet::new();
+
+        for (prov, province) in self.provinces.iter() {
+            if !province.home_sc.is_empty() && !province.is_sc {
+                problems.insert(MapProblem::HomeNotSc(prov.to_string()));
+            }
+        }
+
+        for prov in self.starting_state.units.keys() {
+            if !map.provinces.contains_key(prov) {
+                problems.insert(MapProblem::UnknownStartingProvince(prov.to_string()));
+            }
+        }
+
+        let with_units = self.starting_state.units.values()
+            .map(|unit| unit.nationality())
+            .collect::<HashSet<_>>();
+        for power in self.powers.keys() {
+            if !with_units.contains(power) {
+                problems.insert(MapProblem::NoUnits(power.to_string()));
+            }
+        }
+
+        problems.into_iter().collect()
+    }
+}
@@ -0,0 +1,205 @@
+//! Compiled representation of a [`Map`].
+//!
+//! [`Map`] stores adjacencies as sets of string tuples, which is convenient for serialization,
+//! but every lookup has to allocate the strings of the tuple.
+//! [`CompiledMap`] assigns integer ids to provinces and locations, and stores adjacency lists.
+//! The adjudicator uses it for all adjacency checks; callers adjudicating many positions on the
+//! same map can compile it once and use [`adjudicate_compiled`](crate::adjudicate_compiled).
+
+use std::{collections::HashMap, ops::Range};
+
+use crate::{Map, Province, ProvinceAbbr};
+
+/// Identifier of a province in a [`CompiledMap`].
+pub type ProvinceId = usize;
+
+/// Identifier of a location (a province and a coast) in a [`CompiledMap`].
+/// The location with the empty coast stands for the province as a whole.
+pub type LocId = usize;
+
+/// A [`Map`] with interned provinces and locations.
+///
+/// Ids are assigned in alphabetical order.
+#[derive(Clone)]
+pub struct CompiledMap {
+    /// The provinces of the map.
+    pub provinces: HashMap<ProvinceAbbr, Province>,
+
+    province_ids: HashMap<String, ProvinceId>,
+    province_names: Vec<String>,
+    /// Sorted coasts of each province, including the empty coast.
+    coasts: Vec<Vec<String>>,
+    /// Id of the first location of each province; the others follow in the order of `coasts`.
+    first_loc: Vec<LocId>,
+    /// Province of each location.
+    loc_provinces: Vec<ProvinceId>,
+
+    /// Sorted army adjacency lists, by province.
+    army_adj: Vec<Vec<ProvinceId>>,
+    /// Sorted fleet adjacency lists, by location, including passages through canals.
+    fleet_adj: Vec<Vec<LocId>>,
+    /// Sorted fleet adjacency lists, by location, including only the canals convoys can pass through.
+    convoy_adj: Vec<Vec<LocId>>,
+}
+
+impl CompiledMap {
+    pub fn new(map: &Map) -> Self {
+        // fleets pass through canals in both directions, unless the canal itself is impassable
+        let canal_adj = map.provinces.values()
+            .filter(|province| !province.impassable)
+            .filter_map(|province| province.canal.as_ref())
+            .flat_map(|canal| canal.connects.iter().flat_map(|(a, b)| [(a, b, canal.convoys), (b, a, canal.convoys)]))
+            .collect::<Vec<_>>();
+
+        // provinces referred to by adjacencies are interned even if they are not on the map
+        let mut names = HashMap::new();
+        let adj_names = map.army_adj.iter().flat_map(|(a, b)| [a, b])
+            .chain(map.fleet_adj.iter().flat_map(|((a, _), (b, _))| [a, b]))
+            .chain(canal_adj.iter().map(|((a, _), _, _)| a));
+        for name in map.provinces.keys().chain(adj_names) {
+            names.insert(name.as_str(), 0);
+        }
+        let mut province_names = names.keys().copied().collect::<Vec<_>>();
+        province_names.sort();
+        for (id, name) in province_names.iter().enumerate() {
+            names.insert(name, id);
+        }
+
+        let mut coasts = vec![vec![""]; province_names.len()];
+        let mut add_coast = |prov: &str, coast| {
+            let coasts = &mut coasts[names[prov]];
+            if !coasts.contains(&coast) {
+                coasts.push(coast);
+            }
+        };
+        for (name, province) in map.provinces.iter() {
+            for coast in province.coasts.iter() {
+                add_coast(name, coast);
+            }
+        }
+        for ((a, a_coast), (b, b_coast)) in map.fleet_adj.iter() {
+            add_coast(a, a_coast);
+            add_coast(b, b_coast);
+        }
+        for ((a, a_coast), _, _) in canal_adj.iter() {
+            add_coast(a, a_coast);
+        }
+
+        let mut first_loc = vec![];
+        let mut loc_provinces = vec![];
+        for (prov, coasts) in coasts.iter_mut().enumerate() {
+            coasts.sort();
+            first_loc.push(loc_provinces.len());
+            loc_provinces.extend(coasts.iter().map(|_| prov));
+        }
+        let loc_id = |prov: &str, coast: &str| {
+            let prov = names[prov];
+            first_loc[prov] + coasts[prov].binary_search(&coast).unwrap()
+        };
+
+        // adjacencies involving impassable provinces, or against one-way passages, are left out
+        let mut army_adj = vec![vec![]; province_names.len()];
+        for (a, b) in map.army_adj.iter().filter(|(a, b)| map.passable(a, b)) {
+            army_adj[names[a.as_str()]].push(names[b.as_str()]);
+        }
+        let mut fleet_adj = vec![vec![]; loc_provinces.len()];
+        for ((a, a_coast), (b, b_coast)) in map.fleet_adj.iter().filter(|((a, _), (b, _))| map.passable(a, b)) {
+            fleet_adj[loc_id(a, a_coast)].push(loc_id(b, b_coast));
+        }
+        let mut convoy_adj = fleet_adj.clone();
+        for ((a, a_coast), (b, b_coast), convoys) in canal_adj.into_iter().filter(|((a, _), (b, _), _)| map.passable(a, b)) {
+            let (a, b) = (loc_id(a, a_coast), loc_id(b, b_coast));
+            fleet_adj[a].push(b);
+            if convoys {
+                convoy_adj[a].push(b);
+            }
+        }
+        for adj in army_adj.iter_mut().chain(fleet_adj.iter_mut()).chain(convoy_adj.iter_mut()) {
+            adj.sort();
+            adj.dedup();
+        }
+
+        CompiledMap {
+            provinces: map.provinces.clone(),
+            province_ids: names.into_iter().map(|(name, id)| (name.to_string(), id)).collect(),
+            province_names: province_names.into_iter().map(str::to_string).collect(),
+            coasts: coasts.into_iter()
+                .map(|coasts| coasts.into_iter().map(str::to_string).collect())
+                .collect(),
+            first_loc,
+            loc_provinces,
+            army_adj,
+            fleet_adj,
+            convoy_adj,
+        }
+    }
+
+    pub fn province_id(&self, prov: &str) -> Option<ProvinceId> {
+        self.province_ids.get(prov).copied()
+    }
+
+    pub fn province_name(&self, id: ProvinceId) -> &str {
+        &self.province_names[id]
+    }
+
+    /// The number of provinces; ids range from 0 to this number.
+    pub fn province_count(&self) -> usize {
+        self.province_names.len()
+    }
+
+    /// The number of locations; ids range from 0 to this number.
+    pub fn loc_count(&self) -> usize {
+        self.loc_provinces.len()
+    }
+
+    /// The locations of a province, starting with the province as a whole.
+    pub fn locs(&self, prov: ProvinceId) -> Range<LocId> {
+        self.first_loc[prov]..self.first_loc[prov] + self.coasts[prov].len()
+    }
+
+    pub fn loc_province(&self, loc: LocId) -> ProvinceId {
+        self.loc_provinces[loc]
+    }
+
+    pub fn loc_id(&self, prov: &str, coast: &str) -> Option<LocId> {
+        let prov = self.province_id(prov)?;
+        let index = self.coasts[prov].binary_search_by(|c| c.as_str().cmp(coast)).ok()?;
+        Some(self.first_loc[prov] + index)
+    }
+
+    /// The province name and coast of a location.
+    pub fn loc(&self, id: LocId) -> (&str, &str) {
+        let prov = self.loc_provinces[id];
+        (&self.province_names[prov], &self.coasts[prov][id - self.first_loc[prov]])
+    }
+
+    /// The provinces an army in `prov` can move to.
+    pub fn army_neighbours(&self, prov: ProvinceId) -> &[ProvinceId] {
+        &self.army_adj[prov]
+    }
+
+    /// The locations a fleet in `loc` can move to.
+    pub fn fleet_neighbours(&self, loc: LocId) -> &[LocId] {
+        &self.fleet_adj[loc]
+    }
+
+    /// The locations a convoy can be passed on to from `loc`.
+    /// These are the fleet adjacencies, except for passages through canals that don't allow convoys.
+    pub fn convoy_neighbours(&self, loc: LocId) -> &[LocId] {
+        &self.convoy_adj[loc]
+    }
+
+    pub fn army_adjacent(&self, src: &str, dest: &str) -> bool {
+        match (self.province_id(src), self.province_id(dest)) {
+            (Some(src), Some(dest)) => self.army_adj[src].binary_search(&dest).is_ok(),
+            _ => false,
+        }
+    }
+
+    pub fn fleet_adjacent(&self, src: &str, src_coast: &str, dest: &str, dest_coast: &str) -> bool {
+        match (self.loc_id(src, src_coast), self.loc_id(dest, dest_coast)) {
+            (Some(src), Some(dest)) => self.fleet_adj[src].binary_search(&dest).is_ok(),
+            _ => false,
+        }
+    }
+}
@@ -2,7 +2,7 @@
 
 use serde::{Deserialize, Serialize};
 
-use crate::{base, Order};
+use crate::{base, CompiledMap, IndexedOrders, Order, RuleSet};
 
 /// A core order. Succeeds if untapped.
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -12,9 +12,10 @@ pub struct Core;
 impl Order for Core {
     fn deps(
             &self,
-            map: &crate::Map,
+            map: &CompiledMap,
+            _rules: &RuleSet,
             state: &crate::MapState,
-            orders: &crate::Orders,
+            orders: &IndexedOrders,
             this_prov: &str,
         ) -> std::collections::HashSet<String> {
         base::deps_for_tap(map, state, orders, this_prov)
@@ -22,13 +23,14 @@ impl Order for Core {
 
     fn adjudicate(
             &self,
-            map: &crate::Map,
+            map: &CompiledMap,
+            rules: &RuleSet,
             state: &crate::MapState,
-            orders: &crate::Orders,
+            orders: &IndexedOrders,
             this_prov: &str,
             order_status: &std::collections::HashMap<String, bool>,
         ) -> Option<bool> {
-        base::is_untapped(map, state, orders, order_status, this_prov, "")
+        base::is_untapped(map, rules, state, orders, order_status, this_prov, "")
     }
 
     fn as_owned(&self) -> Box<dyn Order> {
@@ -0,0 +1,223 @@
+//! Structured explanations of adjudication results.
+//!
+//! Use [`adjudicate_explained`](crate::adjudicate_explained) to obtain a report
+//! for every order, describing the strengths involved, which supports counted,
+//! and how the result was reached.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    base::{
+        compute_attack_strength, compute_defend_strength, compute_hold_strength,
+        compute_prevent_strength, is_convoy_path, is_direct_path, is_head_to_head, is_path, Bounds,
+        Move, SupportHold, SupportMove,
+    },
+    CompiledMap, IndexedOrders, MapState, RuleSet,
+};
+
+/// How the result of an order was reached.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Resolution {
+    /// Resolved from the results of the orders it depends on.
+    Direct,
+    /// Resolved as part of a circular movement.
+    Cycle,
+    /// Resolved by the convoy paradox rule.
+    ConvoyParadox,
+    /// Not resolved by the adjudicator.
+    Unresolved,
+}
+
+/// The kind of strength a move had to overcome.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrengthKind {
+    Hold,
+    Defend,
+    Prevent,
+}
+
+/// A strength opposing a move order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Opposition {
+    /// The province of the opposing unit.
+    pub prov: String,
+    pub kind: StrengthKind,
+    pub strength: Bounds,
+}
+
+/// A support order given to another order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct SupportReport {
+    /// The province of the supporting unit.
+    pub prov: String,
+    /// Whether the support order succeeded.
+    pub success: Option<bool>,
+    /// Whether the support was added to the strength of the supported order.
+    /// A successful support does not count when it would help dislodge a unit
+    /// of the supporting power.
+    pub counted: bool,
+    /// The provinces of the units that cut the support.
+    pub cut_by: Vec<String>,
+}
+
+/// Details specific to a move order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MoveReport {
+    pub attack: Bounds,
+    pub defend: Bounds,
+    pub prevent: Bounds,
+    pub head_to_head: Option<bool>,
+    /// Whether the unit can reach its destination without a convoy.
+    pub direct_path: bool,
+    /// Whether a successful convoy path exists.
+    pub convoy_path: Option<bool>,
+    /// The strengths this move had to beat.
+    pub opposition: Vec<Opposition>,
+}
+
+/// Explanation of the result of a single order.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrderReport {
+    pub success: Option<bool>,
+    pub resolution: Resolution,
+    /// The hold strength of the province the order was given in.
+    pub hold: Bounds,
+    /// Present iff the order is a move order.
+    pub movement: Option<MoveReport>,
+    /// Support orders given to this order.
+    pub supports: Vec<SupportReport>,
+    /// If this order is a support order, the provinces of the units that cut it.
+    pub cut_by: Vec<String>,
+}
+
+/// Compute the provinces of the units that cut the support order in `this_prov`.
+fn support_cut_by(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &HashMap<String, bool>, this_prov: &str) -> Vec<String> {
+    let exception = match orders[this_prov].downcast_ref::<SupportMove>() {
+        Some(sup) => sup.dest.as_str(),
+        None if orders[this_prov].is::<SupportHold>() => "",
+        None => return vec![],
+    };
+
+    let mut cut_by = vec![];
+    for prov_it in orders.moves_to(this_prov) {
+        let prov_it = *prov_it;
+        if state.units[prov_it].nationality() == state.units[this_prov].nationality() {
+            continue;
+        }
+
+        let cuts = if prov_it == exception {
+            order_status.get(prov_it) == Some(&true)
+        } else {
+            is_path(map, rules, state, orders, order_status, prov_it) == Some(true)
+        };
+        if cuts {
+            cut_by.push(prov_it.to_string());
+        }
+    }
+    cut_by.sort();
+    cut_by
+}
+
+/// Compute the reports of the support orders given to the order in `this_prov`.
+fn supports_for(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &HashMap<String, bool>, this_prov: &str) -> Vec<SupportReport> {
+    let mov = orders[this_prov].downcast_ref::<Move>();
+
+    // nationality of a unit that would be dislodged by the supported move
+    let defender = mov.and_then(|mov| {
+        let dest_moves_away = orders.get(&mov.dest.0).map(|o| o.is::<Move>()).unwrap_or(false)
+            && order_status.get(&mov.dest.0) == Some(&true);
+        if dest_moves_away {
+            None
+        } else {
+            state.units.get(&mov.dest.0).map(|u| u.nationality())
+        }
+    });
+
+    let supporters = match mov {
+        Some(mov) => orders.support_moves(this_prov, &mov.dest.0),
+        None => orders.support_holds(this_prov),
+    };
+
+    let mut supports = vec![];
+    for &prov_it in supporters {
+        let success = order_status.get(prov_it).copied();
+        supports.push(SupportReport {
+            prov: prov_it.to_string(),
+            success,
+            counted: success == Some(true) && defender != Some(state.units[prov_it].nationality()),
+            cut_by: support_cut_by(map, rules, state, orders, order_status, prov_it),
+        });
+    }
+    supports
+}
+
+fn move_report(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &HashMap<String, bool>, this_prov: &str, mov: &Move) -> MoveReport {
+    let head_to_head = is_head_to_head(map, rules, state, orders, order_status, this_prov);
+
+    let mut opposition = vec![Opposition {
+        prov: mov.dest.0.clone(),
+        kind: StrengthKind::Hold,
+        strength: compute_hold_strength(map, state, orders, order_status, &mov.dest.0),
+    }];
+    if head_to_head == Some(true) {
+        opposition.push(Opposition {
+            prov: mov.dest.0.clone(),
+            kind: StrengthKind::Defend,
+            strength: compute_defend_strength(map, rules, state, orders, order_status, &mov.dest.0),
+        });
+    }
+
+    for prov_it in orders.moves_to(&mov.dest.0) {
+        if *prov_it == this_prov {
+            continue;
+        }
+        opposition.push(Opposition {
+            strength: compute_prevent_strength(map, rules, state, orders, order_status, prov_it),
+            prov: prov_it.to_string(),
+            kind: StrengthKind::Prevent,
+        });
+    }
+
+    MoveReport {
+        attack: compute_attack_strength(map, rules, state, orders, order_status, this_prov),
+        defend: compute_defend_strength(map, rules, state, orders, order_status, this_prov),
+        prevent: compute_prevent_strength(map, rules, state, orders, order_status, this_prov),
+        head_to_head,
+        direct_path: is_direct_path(map, state, orders, this_prov),
+        convoy_path: is_convoy_path(map, rules, state, orders, order_status, this_prov),
+        opposition,
+    }
+}
+
+/// Build reports for every order, given the final adjudication results.
+pub(crate) fn explain(
+    map: &CompiledMap,
+    rules: &RuleSet,
+    state: &MapState,
+    orders: &IndexedOrders,
+    order_status: &HashMap<String, bool>,
+    resolutions: &HashMap<String, Resolution>,
+) -> HashMap<String, OrderReport> {
+    let mut reports = HashMap::new();
+    for (prov, order) in orders.iter() {
+        let mov = order.downcast_ref::<Move>();
+        // a move to the province the unit is already in never leaves it
+        let movement = mov
+            .filter(|mov| mov.dest.0 != *prov)
+            .map(|mov| move_report(map, rules, state, orders, order_status, prov, mov));
+
+        reports.insert(prov.to_string(), OrderReport {
+            success: order_status.get(prov).copied(),
+            resolution: resolutions.get(prov).copied().unwrap_or(Resolution::Unresolved),
+            hold: compute_hold_strength(map, state, orders, order_status, prov),
+            movement,
+            supports: supports_for(map, rules, state, orders, order_status, prov),
+            cut_by: support_cut_by(map, rules, state, orders, order_status, prov),
+        });
+    }
+    reports
+}
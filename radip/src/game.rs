@@ -0,0 +1,288 @@
+//! A turn engine for running complete games.
+//!
+//! [`Game`] owns the sequence of phases (Spring, Fall and their retreats, then Winter),
+//! adjudicates each phase when [`Game::advance`] is called,
+//! and keeps supply center ownership up to date.
+
+use std::{collections::HashMap, error::Error, fmt::Display, ops::Deref};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    adjudicate,
+    adjustment::{adjudicate_adjustments, validate_builds, BuildError, Builds},
+    base::Hold,
+    retreat::{adjudicate_retreats, retreat_dest, RetreatOrder, RetreatOutcome},
+    utils::{apply_adjudication, MapMeta, RetreatOptions},
+    validate_order, AdjudicationError, CompiledMap, Map, MapState, Order, OrderError, Orders, RuleSet,
+};
+
+#[derive(Clone, Serialize, Debug, Deserialize, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum GamePhase {
+    Spring,
+    SpringRetreat,
+    Fall,
+    FallRetreat,
+    Winter,
+}
+
+impl GamePhase {
+    pub fn is_move(self) -> bool {
+        match self {
+            GamePhase::Fall | GamePhase::Spring => true,
+            GamePhase::SpringRetreat | GamePhase::FallRetreat => false,
+            GamePhase::Winter => false,
+        }
+    }
+    pub fn is_retreat(self) -> bool {
+        match self {
+            GamePhase::Fall | GamePhase::Spring => false,
+            GamePhase::SpringRetreat | GamePhase::FallRetreat => true,
+            GamePhase::Winter => false,
+        }
+    }
+    pub fn is_build(self) -> bool {
+        match self {
+            GamePhase::Fall | GamePhase::Spring => false,
+            GamePhase::SpringRetreat | GamePhase::FallRetreat => false,
+            GamePhase::Winter => true,
+        }
+    }
+
+    pub fn next(self, year: u8) -> (Self, u8) {
+        match self {
+            GamePhase::Spring => (GamePhase::SpringRetreat, year),
+            GamePhase::SpringRetreat => (GamePhase::Fall, year),
+            GamePhase::Fall => (GamePhase::FallRetreat, year),
+            GamePhase::FallRetreat => (GamePhase::Winter, year),
+            GamePhase::Winter => (GamePhase::Spring, year + 1),
+        }
+    }
+    pub fn mvmt(self) -> Self {
+        match self {
+            GamePhase::FallRetreat | GamePhase::Fall => GamePhase::Fall,
+            GamePhase::SpringRetreat | GamePhase::Spring => GamePhase::Spring,
+            GamePhase::Winter => GamePhase::Winter,
+        }
+    }
+}
+
+/// Give ownership of every occupied supply center to the occupying power.
+pub fn update_ownership(meta: &MapMeta, state: &mut MapState) {
+    for (prov, unit) in state.units.iter() {
+        if meta.provinces.get(prov).map(|p| p.is_sc).unwrap_or(false) {
+            state.ownership.insert(prov.to_string(), unit.nationality());
+        }
+    }
+}
+
+/// Reason a set of orders was rejected by [`Game`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SubmitError {
+    /// The orders are not of the kind the current phase requires.
+    WrongPhase(GamePhase),
+    /// There is no unit of the power in the given province.
+    NotYourUnit(String),
+    /// The given province is not a legal retreat.
+    InvalidRetreat(String),
+    /// The order given to the unit in the province is illegal.
+    InvalidOrder(String, OrderError),
+    Build(BuildError),
+}
+
+impl Display for SubmitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SubmitError::WrongPhase(phase) => write!(f, "Orders not allowed in phase {:?}", phase),
+            SubmitError::NotYourUnit(prov) => write!(f, "You do not have a unit at {}", prov),
+            SubmitError::InvalidRetreat(prov) => write!(f, "Invalid retreat for {}", prov),
+            SubmitError::InvalidOrder(prov, err) => write!(f, "Invalid order for {}: {}", prov, err),
+            SubmitError::Build(err) => err.fmt(f),
+        }
+    }
+}
+
+impl Error for SubmitError {}
+
+impl From<BuildError> for SubmitError {
+    fn from(err: BuildError) -> Self {
+        SubmitError::Build(err)
+    }
+}
+
+/// The result of adjudicating a phase.
+#[derive(Clone, Debug)]
+pub enum PhaseResult {
+    Movement {
+        orders: Orders,
+        order_status: HashMap<String, bool>,
+        retreats: HashMap<String, RetreatOptions>,
+    },
+    Retreat {
+        orders: HashMap<String, RetreatOrder>,
+        outcomes: HashMap<String, RetreatOutcome>,
+    },
+    Adjustment {
+        builds: Builds,
+    },
+}
+
+/// A game in progress.
+#[derive(Clone)]
+pub struct Game {
+    pub map: Map,
+    pub meta: MapMeta,
+    pub rules: RuleSet,
+
+    pub year: u8,
+    pub phase: GamePhase,
+    pub state: MapState,
+
+    /// The dislodged units of the last movement phase.
+    pub retreats: HashMap<String, RetreatOptions>,
+
+    /// [`Game::map`], compiled once for validating orders.
+    compiled: CompiledMap,
+    orders: Orders,
+    retreat_orders: HashMap<String, RetreatOrder>,
+    builds: Builds,
+}
+
+impl Game {
+    /// Start a game in Spring of year 1, using the variant's starting position
+    /// and rule set. If the variant's rule set is invalid, the default rule set is used.
+    pub fn new(map: Map, meta: MapMeta) -> Self {
+        let state = meta.starting_state.clone();
+        let rules = RuleSet::from_meta(&meta).unwrap_or_default();
+        let compiled = CompiledMap::new(&map);
+        Game {
+            map,
+            meta,
+            rules,
+            year: 1,
+            phase: GamePhase::Spring,
+            state,
+            retreats: HashMap::new(),
+            compiled,
+            orders: HashMap::new(),
+            retreat_orders: HashMap::new(),
+            builds: HashMap::new(),
+        }
+    }
+
+    /// Submit movement or retreat orders for the given power,
+    /// replacing any orders previously given to the same units.
+    /// Illegal movement orders are rejected; see [`validate_order`].
+    ///
+    /// During a retreat phase, move orders are retreats and any other order is a disband.
+    /// Retreats are checked and completed with a coast as in [`adjudicate_retreats`].
+    pub fn submit_orders(&mut self, power: &str, orders: Orders) -> Result<(), SubmitError> {
+        let mut sorted = orders.iter().collect::<Vec<_>>();
+        sorted.sort_by_key(|(prov, _)| *prov);
+        if self.phase.is_move() {
+            for (prov, order) in sorted {
+                if self.state.units.get(prov).map(|u| u.nationality()).as_deref() != Some(power) {
+                    return Err(SubmitError::NotYourUnit(prov.to_string()));
+                }
+                validate_order(&self.compiled, &self.rules, &self.state, prov, order.deref())
+                    .map_err(|err| SubmitError::InvalidOrder(prov.to_string(), err))?;
+            }
+            self.orders.extend(orders);
+        } else if self.phase.is_retreat() {
+            let mut retreat_orders = HashMap::new();
+            for (prov, order) in sorted {
+                let options = match self.retreats.get(prov) {
+                    Some(options) if options.src.nationality() == power => options,
+                    _ => return Err(SubmitError::NotYourUnit(prov.to_string())),
+                };
+                let retreat = match RetreatOrder::from_order(order.deref()) {
+                    RetreatOrder::Retreat(dest) => match retreat_dest(&self.map, options, &dest) {
+                        Some(dest) => RetreatOrder::Retreat(dest),
+                        None => return Err(SubmitError::InvalidRetreat(prov.to_string())),
+                    },
+                    RetreatOrder::Disband => RetreatOrder::Disband,
+                };
+                retreat_orders.insert(prov.to_string(), retreat);
+            }
+            self.retreat_orders.extend(retreat_orders);
+        } else {
+            return Err(SubmitError::WrongPhase(self.phase));
+        }
+
+        Ok(())
+    }
+
+    /// Submit the adjustment orders of the given power,
+    /// replacing any adjustment orders it previously submitted.
+    pub fn submit_builds(&mut self, power: &str, builds: Builds) -> Result<(), SubmitError> {
+        if !self.phase.is_build() {
+            return Err(SubmitError::WrongPhase(self.phase));
+        }
+        validate_builds(&self.map, &self.meta, &self.state, power, &builds)?;
+
+        let state = &self.state;
+        self.builds.retain(|prov, _| {
+            let owner = state.units.get(prov).map(|u| u.nationality())
+                .or_else(|| state.ownership.get(prov).cloned());
+            owner.as_deref() != Some(power)
+        });
+        self.builds.extend(builds);
+        Ok(())
+    }
+
+    /// The movement orders submitted so far in this phase.
+    pub fn orders(&self) -> &Orders {
+        &self.orders
+    }
+
+    /// Adjudicate the current phase and move on to the next one.
+    ///
+    /// Units without orders hold, dislodged units without orders disband,
+    /// and powers without adjustment orders waive their builds.
+    /// Retreat phases without dislodged units are skipped.
+    ///
+    /// Fails without changing the game if the board and the orders are inconsistent,
+    /// which can only happen if [`Game::state`] was modified after orders were submitted.
+    pub fn advance(&mut self) -> Result<PhaseResult, AdjudicationError> {
+        let result = if self.phase.is_move() {
+            let mut orders = self.orders.clone();
+            for prov in self.state.units.keys() {
+                orders.entry(prov.to_string()).or_insert_with(|| Box::new(Hold) as Box<dyn Order>);
+            }
+
+            let order_status = adjudicate(&self.map, &self.rules, &self.state, &orders)?;
+            let (new_state, retreats) = apply_adjudication(&self.map, &self.rules, &self.state, &orders, &order_status)?;
+            self.orders.clear();
+            self.state = new_state;
+            self.retreats = retreats.clone();
+
+            PhaseResult::Movement { orders, order_status, retreats }
+        } else if self.phase.is_retreat() {
+            let orders = std::mem::take(&mut self.retreat_orders);
+            let (new_state, outcomes) = adjudicate_retreats(&self.map, &self.state, &self.retreats, &orders);
+            self.state = new_state;
+            self.retreats.clear();
+
+            PhaseResult::Retreat { orders, outcomes }
+        } else {
+            let builds = std::mem::take(&mut self.builds);
+            let (new_state, builds) = adjudicate_adjustments(&self.map, &self.meta, &self.state, &builds);
+            self.state = new_state;
+
+            PhaseResult::Adjustment { builds }
+        };
+
+        loop {
+            (self.phase, self.year) = self.phase.next(self.year);
+            if self.phase.is_build() {
+                update_ownership(&self.meta, &mut self.state);
+            }
+            if !(self.phase.is_retreat() && self.retreats.is_empty()) {
+                break;
+            }
+        }
+
+        Ok(result)
+    }
+}
@@ -0,0 +1,254 @@
+//! Distances, paths and connectivity on a map.
+//!
+//! The functions in this module treat the map as a graph whose edges are the adjacencies of a [`Movement`],
+//! and search it breadth first, so distances are numbers of moves. Convoys are not taken into account.
+//! Adjacencies are followed in the direction they are given; on a valid map (see [`Map::validate`](crate::Map::validate))
+//! they are symmetric, except for one-way passages.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use crate::{
+    compiled::{LocId, ProvinceId},
+    CompiledMap, FleetLoc, ProvinceAbbr,
+};
+
+/// The adjacencies to move along.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Movement {
+    Army,
+    /// Fleets move between coasts, so a fleet can't move from one coast of a province to another through it.
+    Fleet,
+    /// Either army or fleet adjacencies.
+    Any,
+}
+
+/// The map as a graph for a movement.
+/// Nodes are locations for fleets, and provinces otherwise.
+struct Graph<'a> {
+    map: &'a CompiledMap,
+    movement: Movement,
+    adj: Vec<Vec<usize>>,
+}
+
+/// Distance and predecessor of each node reached by a search.
+type Reached = Vec<Option<(u32, usize)>>;
+
+/// Whether a fleet can be at a location; fleets in provinces with coasts must be on one of them.
+fn is_fleet_loc(map: &CompiledMap, loc: LocId) -> bool {
+    let (_, coast) = map.loc(loc);
+    !coast.is_empty() || map.locs(map.loc_province(loc)).len() == 1
+}
+
+impl<'a> Graph<'a> {
+    fn new(map: &'a CompiledMap, movement: Movement) -> Self {
+        let adj = match movement {
+            Movement::Army => (0..map.province_count())
+                .map(|prov| map.army_neighbours(prov).to_vec())
+                .collect(),
+            Movement::Fleet => (0..map.loc_count())
+                .map(|loc| map.fleet_neighbours(loc).iter().copied().filter(|dest| is_fleet_loc(map, *dest)).collect())
+                .collect(),
+            Movement::Any => (0..map.province_count())
+                .map(|prov| {
+                    let mut adj = map.army_neighbours(prov).to_vec();
+                    adj.extend(map.locs(prov).flat_map(|loc| map.fleet_neighbours(loc)).map(|dest| map.loc_province(*dest)));
+                    adj.sort();
+                    adj.dedup();
+                    adj
+                })
+                .collect(),
+        };
+        Graph { map, movement, adj }
+    }
+
+    /// The same graph with every edge also followed backwards.
+    fn undirected(mut self) -> Self {
+        for node in 0..self.adj.len() {
+            for next in self.adj[node].clone() {
+                if !self.adj[next].contains(&node) {
+                    self.adj[next].push(node);
+                }
+            }
+        }
+        self
+    }
+
+    /// The nodes of a province.
+    fn nodes(&self, prov: ProvinceId) -> Vec<usize> {
+        match self.movement {
+            Movement::Fleet => self.map.locs(prov).filter(|loc| is_fleet_loc(self.map, *loc)).collect(),
+            _ => vec![prov],
+        }
+    }
+
+    fn province_nodes<'b>(&self, provs: impl IntoIterator<Item = &'b str>) -> Vec<usize> {
+        provs.into_iter()
+            .filter_map(|prov| self.map.province_id(prov))
+            .flat_map(|prov| self.nodes(prov))
+            .collect()
+    }
+
+    fn province(&self, node: usize) -> ProvinceId {
+        match self.movement {
+            Movement::Fleet => self.map.loc_province(node),
+            _ => node,
+        }
+    }
+
+    fn search(&self, from: impl IntoIterator<Item = usize>) -> Reached {
+        let mut reached = vec![None; self.adj.len()];
+        let mut queue = VecDeque::new();
+        for node in from {
+            if reached[node].is_none() {
+                reached[node] = Some((0, node));
+                queue.push_back(node);
+            }
+        }
+
+        while let Some(node) = queue.pop_front() {
+            let (dist, _) = reached[node].unwrap();
+            for next in self.adj[node].iter().copied() {
+                if reached[next].is_none() {
+                    reached[next] = Some((dist + 1, node));
+                    queue.push_back(next);
+                }
+            }
+        }
+        reached
+    }
+
+    /// The distance of each province reached by a search, which is the least distance of its nodes.
+    fn province_distances(&self, reached: &Reached) -> HashMap<ProvinceAbbr, u32> {
+        let mut distances = HashMap::new();
+        for (node, reached) in reached.iter().enumerate() {
+            if let Some((dist, _)) = reached {
+                let prov = self.map.province_name(self.province(node)).to_string();
+                let entry = distances.entry(prov).or_insert(*dist);
+                *entry = (*entry).min(*dist);
+            }
+        }
+        distances
+    }
+
+    /// The nearest of the given nodes reached by a search, and the path to it from a source.
+    fn path(reached: &Reached, nodes: impl IntoIterator<Item = usize>) -> Option<Vec<usize>> {
+        let mut node = nodes.into_iter()
+            .filter_map(|node| reached[node].map(|(dist, _)| (dist, node)))
+            .min()?
+            .1;
+
+        let mut path = vec![node];
+        while let Some((dist, prev)) = reached[node] {
+            if dist == 0 {
+                break;
+            }
+            path.push(prev);
+            node = prev;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+fn fleet_loc(map: &CompiledMap, loc: LocId) -> FleetLoc {
+    let (prov, coast) = map.loc(loc);
+    (prov.to_string(), coast.to_string())
+}
+
+/// The number of moves needed to reach each province from the nearest of the given provinces.
+/// Provinces that can't be reached are omitted.
+///
+/// Fleets start from every coast of the given provinces.
+pub fn distances<'a>(map: &CompiledMap, movement: Movement, from: impl IntoIterator<Item = &'a str>) -> HashMap<ProvinceAbbr, u32> {
+    let graph = Graph::new(map, movement);
+    let reached = graph.search(graph.province_nodes(from));
+    graph.province_distances(&reached)
+}
+
+/// The number of moves a fleet needs to reach each location from the nearest of the given locations.
+/// Locations that can't be reached are omitted.
+pub fn fleet_distances<'a>(map: &CompiledMap, from: impl IntoIterator<Item = (&'a str, &'a str)>) -> HashMap<FleetLoc, u32> {
+    let graph = Graph::new(map, Movement::Fleet);
+    let reached = graph.search(from.into_iter().filter_map(|(prov, coast)| map.loc_id(prov, coast)));
+    reached.iter().enumerate()
+        .filter_map(|(loc, reached)| reached.map(|(dist, _)| (fleet_loc(map, loc), dist)))
+        .collect()
+}
+
+/// A shortest sequence of provinces leading from `src` to `dest`, including both.
+/// Returns `None` if `dest` can't be reached.
+pub fn shortest_path(map: &CompiledMap, movement: Movement, src: &str, dest: &str) -> Option<Vec<ProvinceAbbr>> {
+    let graph = Graph::new(map, movement);
+    let reached = graph.search(graph.province_nodes([src]));
+    let path = Graph::path(&reached, graph.province_nodes([dest]))?;
+    Some(path.into_iter().map(|node| map.province_name(graph.province(node)).to_string()).collect())
+}
+
+/// A shortest sequence of locations leading a fleet from `src` to `dest`, including both.
+/// Returns `None` if `dest` can't be reached.
+pub fn fleet_shortest_path(map: &CompiledMap, src: (&str, &str), dest: (&str, &str)) -> Option<Vec<FleetLoc>> {
+    let graph = Graph::new(map, Movement::Fleet);
+    let reached = graph.search(map.loc_id(src.0, src.1));
+    let path = Graph::path(&reached, map.loc_id(dest.0, dest.1))?;
+    Some(path.into_iter().map(|loc| fleet_loc(map, loc)).collect())
+}
+
+/// The provinces that can be reached from `from` in at most `moves` moves, including `from` itself.
+pub fn reachable(map: &CompiledMap, movement: Movement, from: &str, moves: u32) -> HashSet<ProvinceAbbr> {
+    distances(map, movement, [from]).into_iter()
+        .filter(|(_, dist)| *dist <= moves)
+        .map(|(prov, _)| prov)
+        .collect()
+}
+
+/// Whether a unit moving along `movement` can be in a province.
+fn can_occupy(map: &CompiledMap, graph: &Graph, prov: ProvinceId) -> bool {
+    let province = match map.provinces.get(map.province_name(prov)) {
+        Some(province) => province,
+        None => return false,
+    };
+    if province.impassable {
+        return false;
+    }
+    match graph.movement {
+        Movement::Army => !province.is_sea,
+        Movement::Fleet => province.is_sea || graph.nodes(prov).iter().any(|loc| !graph.adj[*loc].is_empty()),
+        Movement::Any => true,
+    }
+}
+
+/// The connected components of the provinces a unit moving along `movement` can be in.
+///
+/// Armies can be in land provinces, and fleets in seas and provinces with fleet adjacencies.
+/// No unit can be in an impassable province.
+/// One-way passages connect the provinces at both of their ends, so the components don't depend on the order of the provinces.
+/// Components are sorted, and ordered by their first province.
+pub fn components(map: &CompiledMap, movement: Movement) -> Vec<Vec<ProvinceAbbr>> {
+    let graph = Graph::new(map, movement).undirected();
+    let mut visited = vec![false; map.province_count()];
+    let mut components = vec![];
+
+    for prov in 0..map.province_count() {
+        if visited[prov] || !can_occupy(map, &graph, prov) {
+            continue;
+        }
+
+        let reached = graph.search(graph.nodes(prov));
+        let mut component = vec![];
+        for (node, reached) in reached.iter().enumerate() {
+            let prov = graph.province(node);
+            if reached.is_some() && !visited[prov] {
+                visited[prov] = true;
+                component.push(map.province_name(prov).to_string());
+            }
+        }
+        component.sort();
+        components.push(component);
+    }
+    components
+}
+
+/// Whether the provinces a unit moving along `movement` can be in form a single component (see [`components`]).
+pub fn is_connected(map: &CompiledMap, movement: Movement) -> bool {
+    components(map, movement).len() <= 1
+}
@@ -0,0 +1,4 @@
+//! Importers and exporters for variant maps in the formats of other adjudicators.
+
+pub mod jdip;
+pub mod judge;
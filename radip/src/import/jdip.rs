@@ -0,0 +1,144 @@
+//! Import of jDip adjacency files.
+//!
+//! jDip describes each province with a `PROVINCE` element, virtually identical to the Judge format:
+//!
+//! ```xml
+//! <PROVINCE shortname="bul" fullname="Bulgaria">
+//!     <UNIQUENAME name="bulg" />
+//!     <ADJACENCY type="mv" refs="gre con ser rum" />
+//!     <ADJACENCY type="ec" refs="con bla rum" />
+//!     <ADJACENCY type="sc" refs="gre aeg con" />
+//! </PROVINCE>
+//! ```
+//!
+//! Adjacencies of type `mv` are army moves, `xc` are fleet moves from a province without coasts,
+//! and any other type is the coast the fleet moves from. References may name a coast, as in `bul-sc`.
+//! Provinces without army moves are seas.
+//! Provinces only adjacent to themselves, such as Switzerland, are impassable; the self adjacencies are left out.
+
+use std::{
+    collections::{HashMap, HashSet},
+    error::Error,
+    fmt::Display,
+};
+
+use crate::{Map, Province, ProvinceAbbr};
+
+/// A map imported from jDip, along with the province data that belongs in
+/// [`ProvinceMeta`](crate::utils::ProvinceMeta).
+pub struct JdipMap {
+    pub map: Map,
+    /// Full name of each province.
+    pub names: HashMap<ProvinceAbbr, String>,
+    /// Alternative names of each province, given by `UNIQUENAME` elements.
+    pub aliases: HashMap<ProvinceAbbr, Vec<String>>,
+}
+
+/// Reason a jDip adjacency file could not be imported.
+#[derive(Debug)]
+pub enum ImportError {
+    Xml(roxmltree::Error),
+    /// An element is missing the given attribute.
+    MissingAttribute(String, &'static str),
+    /// A province is defined more than once.
+    DuplicateProvince(String),
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::Xml(err) => write!(f, "Invalid XML: {}", err),
+            ImportError::MissingAttribute(elem, attr) => write!(f, "{} element is missing the {} attribute", elem, attr),
+            ImportError::DuplicateProvince(prov) => write!(f, "Province {} is defined more than once", prov),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+impl From<roxmltree::Error> for ImportError {
+    fn from(err: roxmltree::Error) -> Self {
+        ImportError::Xml(err)
+    }
+}
+
+fn attribute<'a>(node: roxmltree::Node<'a, '_>, attr: &'static str) -> Result<&'a str, ImportError> {
+    node.attribute(attr).ok_or_else(|| ImportError::MissingAttribute(node.tag_name().name().to_string(), attr))
+}
+
+/// Split a reference such as `bul-sc` into its province and coast.
+fn parse_ref(r: &str) -> (String, String) {
+    match r.split_once('-') {
+        Some((prov, coast)) => (prov.to_lowercase(), coast.to_lowercase()),
+        None => (r.to_lowercase(), "".to_string()),
+    }
+}
+
+/// Parse the `PROVINCES` element of a jDip adjacency file.
+/// Province abbreviations and coasts are converted to lowercase.
+pub fn parse(xml: &str) -> Result<JdipMap, ImportError> {
+    let options = roxmltree::ParsingOptions { allow_dtd: true, ..Default::default() };
+    let doc = roxmltree::Document::parse_with_options(xml, options)?;
+
+    let mut provinces = HashMap::new();
+    let mut names = HashMap::new();
+    let mut aliases = HashMap::new();
+    let mut fleet_adj = HashSet::new();
+    let mut army_adj = HashSet::new();
+
+    for province in doc.descendants().filter(|node| node.has_tag_name("PROVINCE")) {
+        let abbr = attribute(province, "shortname")?.to_lowercase();
+        if provinces.contains_key(&abbr) {
+            return Err(ImportError::DuplicateProvince(abbr));
+        }
+
+        let mut coasts = HashSet::new();
+        let mut is_land = false;
+        let (mut self_adj, mut other_adj) = (false, false);
+        let mut unique_names = vec![];
+        for child in province.children().filter(|node| node.is_element()) {
+            if child.has_tag_name("UNIQUENAME") {
+                unique_names.push(attribute(child, "name")?.to_string());
+                continue;
+            }
+            if !child.has_tag_name("ADJACENCY") {
+                continue;
+            }
+
+            let kind = attribute(child, "type")?.to_lowercase();
+            let (self_refs, refs): (Vec<_>, Vec<_>) = attribute(child, "refs")?.split_whitespace()
+                .map(parse_ref)
+                .partition(|(dest, _)| *dest == abbr);
+            self_adj |= !self_refs.is_empty();
+            other_adj |= !refs.is_empty();
+            let refs = refs.into_iter();
+            if kind == "mv" {
+                is_land = true;
+                army_adj.extend(refs.map(|(dest, _)| (abbr.clone(), dest)));
+                continue;
+            }
+
+            let coast = if kind == "xc" { "".to_string() } else { kind };
+            for (dest, dest_coast) in refs {
+                // fleets can move between coasts, and between the provinces as a whole
+                for src in [(abbr.clone(), coast.clone()), (abbr.clone(), "".to_string())] {
+                    fleet_adj.insert((src.clone(), (dest.clone(), dest_coast.clone())));
+                    fleet_adj.insert((src, (dest.clone(), "".to_string())));
+                }
+            }
+            if !coast.is_empty() {
+                coasts.insert(coast);
+            }
+        }
+
+        names.insert(abbr.clone(), attribute(province, "fullname")?.to_string());
+        aliases.insert(abbr.clone(), unique_names);
+        provinces.insert(abbr, Province { coasts, is_sea: !is_land, canal: None, impassable: self_adj && !other_adj });
+    }
+
+    Ok(JdipMap {
+        map: Map { provinces, fleet_adj, army_adj, one_way: HashSet::new() },
+        names,
+        aliases,
+    })
+}
@@ -0,0 +1,284 @@
+//! Import and export of Judge map files.
+//!
+//! A Judge map file has two sections, each terminated by a line containing `-1`.
+//! The first lists the provinces, with their full name, type, abbreviation and aliases:
+//!
+//! ```text
+//! Adriatic Sea,   w adr adriatic
+//! Belgium,        x bel belgium
+//! Berlin,         G ber berlin
+//! ```
+//!
+//! The type is `w` for water, `l` for land, `x` for a supply center that is not a home center,
+//! and the letter of a power for one of its home centers.
+//! The second section lists the adjacencies of each province:
+//!
+//! ```text
+//! bul-mv: gre con ser rum
+//! bul-ec: con bla rum
+//! con-xc: bul/sc bul/ec bla ank smy aeg
+//! ```
+//!
+//! `mv` lines are army moves, `xc` lines are fleet moves from a province without coasts,
+//! and other lines are fleet moves from the given coast. References may name a coast, as in `bul/sc`.
+//! Provinces only adjacent to themselves, such as Switzerland, are impassable.
+//!
+//! Judge map files don't name the powers or give starting units;
+//! the powers are identified by a letter, and the letter of each power must be given.
+
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    error::Error,
+    fmt::{Display, Write},
+};
+
+use crate::{
+    utils::{MapMeta, PowerMeta, ProvinceMeta},
+    Map, MapState, Province,
+};
+
+/// Reason a Judge map file could not be imported.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ImportError {
+    /// The line with the given number is malformed.
+    InvalidLine(usize),
+    /// The province type on the given line is not a known type or power letter.
+    UnknownType(usize, char),
+    /// The line with the given number refers to an undefined province.
+    UnknownProvince(usize, String),
+    /// A province is defined more than once.
+    DuplicateProvince(String),
+    /// The file ends before the `-1` terminating a section.
+    MissingTerminator,
+}
+
+impl Display for ImportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImportError::InvalidLine(line) => write!(f, "Line {} is malformed", line),
+            ImportError::UnknownType(line, kind) => write!(f, "Unknown province type {} on line {}", kind, line),
+            ImportError::UnknownProvince(line, prov) => write!(f, "Unknown province {} on line {}", prov, line),
+            ImportError::DuplicateProvince(prov) => write!(f, "Province {} is defined more than once", prov),
+            ImportError::MissingTerminator => write!(f, "Section is not terminated by -1"),
+        }
+    }
+}
+
+impl Error for ImportError {}
+
+/// Reason a map could not be exported to a Judge map file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ExportError {
+    /// The power owning a home center has no letter.
+    NoLetter(String),
+    /// A province is missing from the metadata.
+    NoMeta(String),
+}
+
+impl Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportError::NoLetter(power) => write!(f, "Power {} has no letter", power),
+            ExportError::NoMeta(prov) => write!(f, "Province {} has no metadata", prov),
+        }
+    }
+}
+
+impl Error for ExportError {}
+
+/// Split a reference such as `bul/sc` into its province and coast.
+fn parse_ref(r: &str) -> (String, String) {
+    match r.split_once(['/', '-']) {
+        Some((prov, coast)) => (prov.to_lowercase(), coast.to_lowercase()),
+        None => (r.to_lowercase(), "".to_string()),
+    }
+}
+
+/// Parse a Judge map file. `powers` gives the power of each letter used for home centers.
+///
+/// The returned metadata has no name, author or starting units.
+/// Province abbreviations, coasts and aliases are converted to lowercase.
+pub fn parse(text: &str, powers: &[(char, &str)]) -> Result<(Map, MapMeta), ImportError> {
+    let mut lines = text.lines().enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty());
+
+    let mut provinces = HashMap::new();
+    let mut province_meta = HashMap::new();
+    loop {
+        let (i, line) = lines.next().ok_or(ImportError::MissingTerminator)?;
+        if line == "-1" {
+            break;
+        }
+
+        let (name, rest) = line.split_once(',').ok_or(ImportError::InvalidLine(i))?;
+        let mut words = rest.split_whitespace();
+        let (kind, abbr) = match (words.next(), words.next()) {
+            (Some(kind), Some(abbr)) if kind.chars().count() == 1 => (kind.chars().next().unwrap(), abbr.to_lowercase()),
+            _ => return Err(ImportError::InvalidLine(i)),
+        };
+        let home_sc = match kind {
+            'w' | 'l' | 'x' => "",
+            _ => powers.iter().find(|(letter, _)| *letter == kind)
+                .map(|(_, power)| *power)
+                .ok_or(ImportError::UnknownType(i, kind))?,
+        };
+
+        if provinces.contains_key(&abbr) {
+            return Err(ImportError::DuplicateProvince(abbr));
+        }
+        provinces.insert(abbr.clone(), Province { coasts: HashSet::new(), is_sea: kind == 'w', canal: None, impassable: false });
+        province_meta.insert(abbr, ProvinceMeta {
+            name: name.trim().to_string(),
+            is_sc: kind != 'w' && kind != 'l',
+            home_sc: home_sc.to_string(),
+            aliases: words.map(|alias| alias.to_lowercase()).collect(),
+            coasts: HashMap::new(),
+        });
+    }
+
+    let mut fleet_adj = HashSet::new();
+    let mut army_adj = HashSet::new();
+    let (mut self_adj, mut other_adj) = (HashSet::new(), HashSet::new());
+    loop {
+        let (i, line) = lines.next().ok_or(ImportError::MissingTerminator)?;
+        if line == "-1" {
+            break;
+        }
+
+        let (src, refs) = line.split_once(':').ok_or(ImportError::InvalidLine(i))?;
+        let (abbr, kind) = src.trim().split_once('-').ok_or(ImportError::InvalidLine(i))?;
+        let (abbr, kind) = (abbr.to_lowercase(), kind.to_lowercase());
+        let (self_refs, refs): (Vec<_>, Vec<_>) = refs.split_whitespace().map(parse_ref).partition(|(dest, _)| *dest == abbr);
+        if let Some(prov) = std::iter::once(&abbr).chain(refs.iter().map(|(dest, _)| dest))
+            .find(|prov| !provinces.contains_key(*prov)) {
+            return Err(ImportError::UnknownProvince(i, prov.to_string()));
+        }
+        if !self_refs.is_empty() {
+            self_adj.insert(abbr.clone());
+        }
+        if !refs.is_empty() {
+            other_adj.insert(abbr.clone());
+        }
+
+        if kind == "mv" {
+            army_adj.extend(refs.into_iter().map(|(dest, _)| (abbr.clone(), dest)));
+            continue;
+        }
+
+        let coast = if kind == "xc" { "".to_string() } else { kind };
+        for (dest, dest_coast) in refs {
+            // fleets can move between coasts, and between the provinces as a whole
+            for src in [(abbr.clone(), coast.clone()), (abbr.clone(), "".to_string())] {
+                fleet_adj.insert((src.clone(), (dest.clone(), dest_coast.clone())));
+                fleet_adj.insert((src, (dest.clone(), "".to_string())));
+            }
+            if !dest_coast.is_empty() {
+                provinces.get_mut(&dest).unwrap().coasts.insert(dest_coast);
+            }
+        }
+        if !coast.is_empty() {
+            provinces.get_mut(&abbr).unwrap().coasts.insert(coast);
+        }
+    }
+
+    for abbr in self_adj.difference(&other_adj) {
+        provinces.get_mut(abbr).unwrap().impassable = true;
+    }
+
+    let meta = MapMeta {
+        name: "".to_string(),
+        author: "".to_string(),
+        powers: powers.iter().map(|(_, power)| (power.to_string(), PowerMeta {
+            name: power.to_string(),
+            tile_color: "".to_string(),
+            sc_color: "".to_string(),
+        })).collect(),
+        starting_state: MapState { units: HashMap::new(), ownership: HashMap::new() },
+        provinces: province_meta,
+        data: HashMap::new(),
+    };
+    Ok((Map { provinces, fleet_adj, army_adj, one_way: HashSet::new() }, meta))
+}
+
+/// Write a map in the Judge format. `powers` gives the letter of each power owning home centers.
+///
+/// The format has no canals, so passages through canals are left out.
+/// Adjacencies the adjudicator ignores because of impassable provinces or one-way passages are left out too,
+/// and impassable provinces are written as adjacent only to themselves.
+pub fn write(map: &Map, meta: &MapMeta, powers: &[(char, &str)]) -> Result<String, ExportError> {
+    let mut abbrs = map.provinces.keys().collect::<Vec<_>>();
+    abbrs.sort();
+
+    let mut out = String::new();
+    for abbr in abbrs.iter() {
+        let province = &map.provinces[*abbr];
+        let province_meta = meta.provinces.get(*abbr).ok_or_else(|| ExportError::NoMeta(abbr.to_string()))?;
+        let kind = if province.is_sea {
+            'w'
+        } else if !province_meta.home_sc.is_empty() {
+            powers.iter().find(|(_, power)| *power == province_meta.home_sc)
+                .map(|(letter, _)| *letter)
+                .ok_or_else(|| ExportError::NoLetter(province_meta.home_sc.to_string()))?
+        } else if province_meta.is_sc {
+            'x'
+        } else {
+            'l'
+        };
+
+        write!(out, "{}, {} {}", province_meta.name, kind, abbr).unwrap();
+        for alias in province_meta.aliases.iter() {
+            write!(out, " {}", alias).unwrap();
+        }
+        out.push('\n');
+    }
+    out.push_str("-1\n");
+
+    // destinations of each location, with the coasts they can be reached at
+    let mut fleet_dests: HashMap<(&str, &str), BTreeMap<&str, BTreeSet<&str>>> = HashMap::new();
+    for ((src, src_coast), (dest, dest_coast)) in map.fleet_adj.iter().filter(|((src, _), (dest, _))| map.passable(src, dest)) {
+        fleet_dests.entry((src, src_coast)).or_default().entry(dest).or_default().insert(dest_coast);
+    }
+
+    for abbr in abbrs.iter() {
+        let province = &map.provinces[*abbr];
+        if province.impassable {
+            writeln!(out, "{}-{}: {}", abbr, if province.is_sea { "xc" } else { "mv" }, abbr).unwrap();
+            continue;
+        }
+
+        let mut army_dests = map.army_adj.iter()
+            .filter(|(src, dest)| src == *abbr && map.passable(src, dest))
+            .map(|(_, dest)| dest.as_str())
+            .collect::<Vec<_>>();
+        army_dests.sort();
+        if !army_dests.is_empty() {
+            writeln!(out, "{}-mv: {}", abbr, army_dests.join(" ")).unwrap();
+        }
+
+        let mut coasts = province.coasts.iter().map(String::as_str).collect::<Vec<_>>();
+        coasts.sort();
+        if coasts.is_empty() {
+            coasts.push("");
+        }
+        for coast in coasts {
+            let dests = match fleet_dests.get(&(abbr.as_str(), coast)) {
+                Some(dests) => dests,
+                None => continue,
+            };
+            let mut refs = vec![];
+            for (dest, dest_coasts) in dests.iter() {
+                if dest_coasts.iter().all(|c| c.is_empty()) {
+                    refs.push(dest.to_string());
+                } else {
+                    refs.extend(dest_coasts.iter().filter(|c| !c.is_empty()).map(|c| format!("{}/{}", dest, c)));
+                }
+            }
+            let kind = if coast.is_empty() { "xc" } else { coast };
+            writeln!(out, "{}-{}: {}", abbr, kind, refs.join(" ")).unwrap();
+        }
+    }
+    out.push_str("-1\n");
+
+    Ok(out)
+}
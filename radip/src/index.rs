@@ -0,0 +1,108 @@
+//! Precomputed lookups into a set of orders.
+//!
+//! Most orders depend on the moves, supports and convoys that refer to a given province.
+//! Finding these by scanning every order makes adjudication quadratic in the number of units,
+//! so the adjudicator builds an [`IndexedOrders`] once per phase and queries it instead.
+
+use std::{collections::HashMap, ops::Deref};
+
+use crate::{
+    base::{Convoy, Move, SupportHold, SupportMove},
+    Orders,
+};
+
+/// Orders together with indices of the moves, supports and convoys they contain.
+///
+/// Dereferences to the underlying [`Orders`].
+/// All lookups return provinces in alphabetical order.
+pub struct IndexedOrders<'a> {
+    orders: &'a Orders,
+    /// Provinces with orders, sorted.
+    provinces: Vec<&'a str>,
+
+    /// Move orders, by destination province.
+    moves_to: HashMap<&'a str, Vec<&'a str>>,
+    /// Support hold orders, by target.
+    support_holds: HashMap<&'a str, Vec<&'a str>>,
+    /// Support move orders, by source and destination of the supported move.
+    support_moves: HashMap<&'a str, HashMap<&'a str, Vec<&'a str>>>,
+    /// Convoy orders, by source and destination of the convoyed army.
+    convoys: HashMap<&'a str, HashMap<&'a str, Vec<&'a str>>>,
+}
+
+impl<'a> IndexedOrders<'a> {
+    pub fn new(orders: &'a Orders) -> Self {
+        let mut provs = orders.keys().map(String::as_str).collect::<Vec<_>>();
+        provs.sort();
+
+        let mut index = IndexedOrders {
+            orders,
+            provinces: provs.clone(),
+            moves_to: HashMap::new(),
+            support_holds: HashMap::new(),
+            support_moves: HashMap::new(),
+            convoys: HashMap::new(),
+        };
+        for prov in provs {
+            let order = &orders[prov];
+            if let Some(mov) = order.downcast_ref::<Move>() {
+                index.moves_to.entry(&mov.dest.0).or_default().push(prov);
+            } else if let Some(sup) = order.downcast_ref::<SupportHold>() {
+                index.support_holds.entry(&sup.target).or_default().push(prov);
+            } else if let Some(sup) = order.downcast_ref::<SupportMove>() {
+                index.support_moves.entry(&sup.src).or_default().entry(&sup.dest).or_default().push(prov);
+            } else if let Some(convoy) = order.downcast_ref::<Convoy>() {
+                index.convoys.entry(&convoy.src).or_default().entry(&convoy.dest).or_default().push(prov);
+            }
+        }
+        index
+    }
+
+    /// The provinces with orders.
+    /// Iterating over these instead of the orders themselves keeps adjudication independent of hash order.
+    pub fn provinces(&self) -> &[&'a str] {
+        &self.provinces
+    }
+
+    /// The provinces of the units ordered to move to `dest`.
+    pub fn moves_to(&self, dest: &str) -> &[&'a str] {
+        self.moves_to.get(dest).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The provinces of the units ordered to support the unit in `target` to hold.
+    pub fn support_holds(&self, target: &str) -> &[&'a str] {
+        self.support_holds.get(target).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// The provinces of the units ordered to support the unit in `src` to move to `dest`.
+    pub fn support_moves(&self, src: &str, dest: &str) -> &[&'a str] {
+        lookup(&self.support_moves, src, dest)
+    }
+
+    /// The provinces of the units ordered to convoy the unit in `src` to `dest`.
+    pub fn convoys(&self, src: &str, dest: &str) -> &[&'a str] {
+        lookup(&self.convoys, src, dest)
+    }
+
+    /// The destination of the move order in `prov`, if there is one.
+    pub fn move_dest(&self, prov: &str) -> Option<&'a str> {
+        self.orders.get(prov)
+            .and_then(|order| order.downcast_ref::<Move>())
+            .map(|mov| mov.dest.0.as_str())
+    }
+}
+
+fn lookup<'b, 'a>(index: &'b HashMap<&'a str, HashMap<&'a str, Vec<&'a str>>>, src: &str, dest: &str) -> &'b [&'a str] {
+    index.get(src)
+        .and_then(|dests| dests.get(dest))
+        .map(Vec::as_slice)
+        .unwrap_or(&[])
+}
+
+impl Deref for IndexedOrders<'_> {
+    type Target = Orders;
+
+    fn deref(&self) -> &Self::Target {
+        self.orders
+    }
+}
@@ -0,0 +1,154 @@
+//! Kruijswijk's guess-and-backtrack resolver,
+//! as described in the article cited in [`base`](crate::base).
+//!
+//! Each order is resolved recursively. When an order depends on its own result,
+//! it is resolved with both a failing and a succeeding guess; if the results differ,
+//! the orders in the cycle are circular movement or a convoy paradox, and are resolved
+//! by the backup rule.
+
+use std::collections::HashMap;
+
+use crate::{
+    base::{Convoy, Move, SupportHold, SupportMove},
+    rules::ConvoyParadox,
+    CompiledMap, IndexedOrders, MapState, RuleSet,
+};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ResolutionState {
+    Unresolved,
+    Guessing,
+    Resolved,
+}
+
+struct Resolver<'a> {
+    map: &'a CompiledMap,
+    rules: &'a RuleSet,
+    state: &'a MapState,
+    orders: &'a IndexedOrders<'a>,
+
+    resolution: HashMap<&'a str, (ResolutionState, bool)>,
+    /// Orders whose result depends on a guess.
+    dep_list: Vec<&'a str>,
+}
+
+impl<'a> Resolver<'a> {
+    /// Adjudicate an order, resolving the orders it depends on.
+    fn adjudicate(&mut self, prov: &'a str) -> bool {
+        let order = &self.orders[prov];
+        let mut deps = order.deps(self.map, self.rules, self.state, self.orders, prov).into_iter()
+            .filter_map(|dep| self.orders.get_key_value(&dep).map(|(dep, _)| dep.as_str()))
+            .collect::<Vec<_>>();
+        deps.sort();
+
+        let mut order_status = HashMap::new();
+        for dep in deps {
+            let result = self.resolve(dep);
+            order_status.insert(dep.to_string(), result);
+        }
+
+        // all dependencies are known, so the order is decided
+        order.adjudicate(self.map, self.rules, self.state, self.orders, prov, &order_status).unwrap_or(false)
+    }
+
+    fn set(&mut self, prov: &'a str, state: ResolutionState, result: bool) {
+        self.resolution.insert(prov, (state, result));
+    }
+
+    fn unresolve_deps(&mut self, old_len: usize) {
+        while self.dep_list.len() > old_len {
+            let prov = self.dep_list.pop().unwrap();
+            let result = self.resolution[prov].1;
+            self.set(prov, ResolutionState::Unresolved, result);
+        }
+    }
+
+    fn resolve(&mut self, prov: &'a str) -> bool {
+        match self.resolution.get(prov).copied().unwrap_or((ResolutionState::Unresolved, false)) {
+            (ResolutionState::Resolved, result) => return result,
+            (ResolutionState::Guessing, result) => {
+                if !self.dep_list.contains(&prov) {
+                    self.dep_list.push(prov);
+                }
+                return result;
+            }
+            (ResolutionState::Unresolved, _) => {}
+        }
+
+        let old_len = self.dep_list.len();
+        self.set(prov, ResolutionState::Guessing, false);
+        let first_result = self.adjudicate(prov);
+
+        if self.dep_list.len() == old_len {
+            // no dependency on a guess, but a backup rule might have resolved this order
+            if self.resolution[prov].0 != ResolutionState::Resolved {
+                self.set(prov, ResolutionState::Resolved, first_result);
+            }
+            return self.resolution[prov].1;
+        }
+
+        if self.dep_list[old_len] != prov {
+            // dependent on a guess, but not on our own
+            self.dep_list.push(prov);
+            self.set(prov, ResolutionState::Guessing, first_result);
+            return first_result;
+        }
+
+        // dependent on our own guess, so try the other guess
+        self.unresolve_deps(old_len);
+        self.set(prov, ResolutionState::Guessing, true);
+        let second_result = self.adjudicate(prov);
+
+        if first_result == second_result {
+            self.unresolve_deps(old_len);
+            self.set(prov, ResolutionState::Resolved, first_result);
+            return first_result;
+        }
+
+        // circular movement or a convoy paradox
+        self.backup_rule(old_len);
+        self.resolve(prov)
+    }
+
+    /// Resolve the orders of a cycle whose result depends on the initial guess.
+    fn backup_rule(&mut self, old_len: usize) {
+        let cycle = self.dep_list.split_off(old_len);
+        let has_convoy = cycle.iter().any(|prov| self.orders[*prov].is::<Convoy>());
+
+        for prov in cycle {
+            let order = &self.orders[prov];
+            let result = if !has_convoy {
+                // circular movement
+                order.is::<Move>().then_some(true)
+            } else {
+                match self.rules.convoy_paradox {
+                    ConvoyParadox::Szykman => order.is::<Convoy>().then_some(false),
+                    ConvoyParadox::AllHold => (order.is::<Move>() || order.is::<SupportHold>()
+                        || order.is::<SupportMove>() || order.is::<Convoy>()).then_some(false),
+                }
+            };
+
+            match result {
+                Some(result) => self.set(prov, ResolutionState::Resolved, result),
+                None => self.set(prov, ResolutionState::Unresolved, false),
+            }
+        }
+    }
+}
+
+/// Adjudicate a movement phase using Kruijswijk's guess-and-backtrack algorithm.
+/// The input must be consistent; see [`check_consistency`](crate::check_consistency).
+pub(crate) fn adjudicate(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders) -> HashMap<String, bool> {
+    let mut resolver = Resolver {
+        map,
+        rules,
+        state,
+        orders,
+        resolution: HashMap::new(),
+        dep_list: Vec::new(),
+    };
+
+    orders.provinces().iter()
+        .map(|prov| (prov.to_string(), resolver.resolve(prov)))
+        .collect()
+}
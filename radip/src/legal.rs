@@ -0,0 +1,115 @@
+//! Enumeration of legal orders.
+//!
+//! [`legal_orders`] lists every order the unit in a province could be given,
+//! such that each listed order passes [`validate_order`](crate::validate_order).
+
+use std::collections::BTreeSet;
+
+use crate::{
+    base::{unit_can_reach, Convoy, Hold, Move, SupportHold, SupportMove},
+    validate::convoying_fleets,
+    CompiledMap, FleetLoc, MapState, Order, RuleSet, Unit,
+};
+
+/// The provinces an army in `src` could be convoyed to.
+/// Equivalent to, but faster than, calling [`has_convoy_route`](crate::validate::has_convoy_route) for every province.
+fn convoy_dests(map: &CompiledMap, rules: &RuleSet, state: &MapState, src: &str) -> BTreeSet<String> {
+    convoying_fleets(map, rules, state, src).iter()
+        .filter_map(|fleet| map.loc_id(fleet, ""))
+        .flat_map(|fleet| map.convoy_neighbours(fleet).iter().map(|b| map.loc(*b)))
+        .filter(|(b, b_coast)| b_coast.is_empty() && *b != src && !map.provinces.get(*b).map(|p| p.is_sea).unwrap_or(true))
+        .map(|(b, _)| b.to_string())
+        .collect()
+}
+
+/// The locations the unit in `prov` can move to, including by convoy.
+fn move_dests(map: &CompiledMap, rules: &RuleSet, state: &MapState, prov: &str) -> BTreeSet<FleetLoc> {
+    let mut dests = BTreeSet::new();
+    match state.units.get(prov) {
+        Some(Unit::Army(_)) => {
+            for dest in map.province_id(prov).iter().flat_map(|id| map.army_neighbours(*id)) {
+                dests.insert((map.province_name(*dest).to_string(), "".to_string()));
+            }
+            for dest in convoy_dests(map, rules, state, prov) {
+                dests.insert((dest, "".to_string()));
+            }
+        }
+        Some(Unit::Fleet(_, coast)) => {
+            for loc in map.loc_id(prov, coast).iter().flat_map(|id| map.fleet_neighbours(*id)) {
+                let (dest, dest_coast) = map.loc(*loc);
+                let coasts = match map.provinces.get(dest) {
+                    Some(p) => &p.coasts,
+                    None => continue,
+                };
+                if coasts.contains(dest_coast) || (dest_coast.is_empty() && coasts.is_empty()) {
+                    dests.insert((dest.to_string(), dest_coast.to_string()));
+                }
+            }
+        }
+        None => {}
+    }
+    dests.remove(&(prov.to_string(), "".to_string()));
+    dests
+}
+
+/// Enumerate the legal orders for the unit in `prov`:
+/// holds, moves (with coasts, and by convoy), supports and convoys.
+/// Armies that could also reach a province by convoy are given a move there via convoy.
+///
+/// Returns no orders if there is no unit in `prov`.
+/// The map is taken compiled, so that callers listing the orders of many units compile it only once.
+pub fn legal_orders(map: &CompiledMap, rules: &RuleSet, state: &MapState, prov: &str) -> Vec<Box<dyn Order>> {
+    let unit = match state.units.get(prov) {
+        Some(unit) if map.provinces.contains_key(prov) => unit,
+        _ => return vec![],
+    };
+
+    let mut orders: Vec<Box<dyn Order>> = vec![Box::new(Hold)];
+    for dest in move_dests(map, rules, state, prov) {
+        orders.push(Box::new(Move { dest, via_convoy: false }));
+    }
+    if matches!(unit, Unit::Army(_)) {
+        // armies may also be convoyed to the provinces they could move to directly
+        for dest in convoy_dests(map, rules, state, prov) {
+            if map.army_adjacent(prov, &dest) {
+                orders.push(Box::new(Move { dest: (dest, "".to_string()), via_convoy: true }));
+            }
+        }
+    }
+
+    let others = state.units.keys()
+        .filter(|other| *other != prov && map.provinces.contains_key(*other))
+        .collect::<BTreeSet<_>>();
+
+    // supports
+    for target in others.iter() {
+        if unit_can_reach(map, state, prov, target) {
+            orders.push(Box::new(SupportHold { target: target.to_string() }));
+        }
+    }
+    for src in others.iter() {
+        let dests = move_dests(map, rules, state, src).into_iter()
+            .map(|(dest, _)| dest)
+            .collect::<BTreeSet<_>>();
+        for dest in dests {
+            if dest != prov && unit_can_reach(map, state, prov, &dest) {
+                orders.push(Box::new(SupportMove { src: src.to_string(), dest }));
+            }
+        }
+    }
+
+    // convoys
+    let can_convoy = map.provinces[prov].is_sea || rules.coastal_convoys;
+    if can_convoy && matches!(unit, Unit::Fleet(_, _)) {
+        for src in others.iter() {
+            if !matches!(state.units[*src], Unit::Army(_)) || !convoying_fleets(map, rules, state, src).contains(prov) {
+                continue;
+            }
+            for dest in convoy_dests(map, rules, state, src) {
+                orders.push(Box::new(Convoy { src: src.to_string(), dest }));
+            }
+        }
+    }
+
+    orders
+}
@@ -1,23 +1,48 @@
 //! DATC-compliant\* diplomacy adjucator.
 //!
 //! Use [`adjudicate`] to adjudicate a movement phase, and [`utils::apply_adjudication`] to update the map.
-//! This crate does not support build phases, as those are fairly easy to implement on your own, and
-//! build phases may differ for different variants.
+//! Retreat phases are adjudicated with [`retreat::adjudicate_retreats`],
+//! and build phases with [`adjustment::adjudicate_adjustments`].
+//! [`game::Game`] runs complete games by chaining these phases together.
+//! Ambiguous rules are interpreted according to a [`RuleSet`].
 
 use std::{
     any::Any,
     collections::{HashMap, HashSet, VecDeque},
-    fmt::Debug,
+    error::Error,
+    fmt::{Debug, Display},
     ops::Deref,
 };
 
+pub mod adjustment;
 pub mod base;
+pub mod check;
+pub mod compiled;
 pub mod core;
+pub mod explain;
+pub mod game;
+pub mod graph;
+pub mod import;
+pub mod index;
+pub mod legal;
+pub mod notation;
+pub mod retreat;
+pub mod rules;
 pub mod utils;
+pub mod validate;
 
+#[cfg(test)]
 mod test;
+mod kruijswijk;
 mod paradox;
 
+pub use check::MapProblem;
+pub use compiled::CompiledMap;
+pub use index::IndexedOrders;
+pub use legal::legal_orders;
+pub use rules::RuleSet;
+pub use validate::{validate_order, OrderError};
+
 /// Abbreviation for a province (e.g. NTH, Lvn).
 pub type ProvinceAbbr = String;
 
@@ -34,6 +59,28 @@ use serde::{Deserialize, Serialize};
 pub struct Province {
     pub coasts: HashSet<String>,
     pub is_sea: bool,
+
+    /// Set if fleets can pass through the province from one body of water to another.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub canal: Option<Canal>,
+
+    /// Set if no unit can enter the province, such as Switzerland.
+    /// Adjacencies to and from the province are ignored.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub impassable: bool,
+}
+
+/// A land province fleets can pass through in a single move, such as a canal or strait.
+///
+/// Fleets can still move into the province itself using the ordinary fleet adjacencies.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Canal {
+    /// Pairs of locations a fleet can move between through the province, in either direction.
+    pub connects: Vec<(FleetLoc, FleetLoc)>,
+
+    /// Whether armies can be convoyed through the province between the connected locations.
+    #[serde(default)]
+    pub convoys: bool,
 }
 
 /// A variant map.
@@ -42,6 +89,11 @@ pub struct Map {
     pub provinces: HashMap<ProvinceAbbr, Province>,
     pub fleet_adj: HashSet<(FleetLoc, FleetLoc)>,
     pub army_adj: HashSet<(ArmyLoc, ArmyLoc)>,
+
+    /// Pairs of provinces units can only move between in the given direction, such as one-way straits.
+    /// Army and fleet adjacencies, and canal passages, from the second province to the first are ignored.
+    #[serde(default, skip_serializing_if = "HashSet::is_empty")]
+    pub one_way: HashSet<(ProvinceAbbr, ProvinceAbbr)>,
 }
 
 impl Map {
@@ -51,6 +103,13 @@ impl Map {
 
         serde_json::from_str(default).unwrap()
     }
+
+    /// Whether adjacencies from `src` to `dest` may be used:
+    /// neither province is impassable, and the move is not against a one-way passage.
+    pub fn passable(&self, src: &str, dest: &str) -> bool {
+        let impassable = |prov: &str| self.provinces.get(prov).is_some_and(|p| p.impassable);
+        !impassable(src) && !impassable(dest) && !self.one_way.contains(&(dest.to_string(), src.to_string()))
+    }
 }
 
 /// Stores the units present on a diplomacy board.
@@ -104,9 +163,10 @@ pub trait Order: 'static + Debug + AsAny + Send + Sync {
     /// for resolution.
     fn deps(
         &self,
-        map: &Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &MapState,
-        orders: &Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
     ) -> HashSet<String>;
 
@@ -114,9 +174,10 @@ pub trait Order: 'static + Debug + AsAny + Send + Sync {
     /// this order succeeds or fails.
     fn adjudicate(
         &self,
-        map: &Map,
+        map: &CompiledMap,
+        rules: &RuleSet,
         state: &MapState,
-        orders: &Orders,
+        orders: &IndexedOrders,
         this_prov: &str,
         order_status: &HashMap<String, bool>,
     ) -> Option<bool>;
@@ -137,9 +198,154 @@ impl Clone for Box<dyn Order> {
     }
 }
 
+/// Reason the input to the adjudicator is inconsistent.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AdjudicationError {
+    /// A unit or order is in a province that is not on the map.
+    UnknownProvince(String),
+    /// There is an order for a province without a unit.
+    NoUnit(String),
+    /// There is a unit without an order.
+    MissingOrder(String),
+    /// The order in the first province refers to the second province,
+    /// which is not on the map.
+    UnknownTarget(String, String),
+}
+
+impl AdjudicationError {
+    /// The province of the offending unit or order.
+    pub fn province(&self) -> &str {
+        match self {
+            AdjudicationError::UnknownProvince(prov)
+            | AdjudicationError::NoUnit(prov)
+            | AdjudicationError::MissingOrder(prov)
+            | AdjudicationError::UnknownTarget(prov, _) => prov,
+        }
+    }
+}
+
+impl Display for AdjudicationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AdjudicationError::UnknownProvince(prov) => write!(f, "Unknown province {}", prov),
+            AdjudicationError::NoUnit(prov) => write!(f, "Order given in {}, but there is no unit", prov),
+            AdjudicationError::MissingOrder(prov) => write!(f, "Unit in {} has no order", prov),
+            AdjudicationError::UnknownTarget(prov, target) => write!(f, "Order in {} refers to unknown province {}", prov, target),
+        }
+    }
+}
+
+impl Error for AdjudicationError {}
+
+/// Provinces referred to by an order, other than the province it is given in.
+fn order_targets(order: &dyn Order) -> Vec<&str> {
+    if let Some(mov) = order.downcast_ref::<base::Move>() {
+        vec![&mov.dest.0]
+    } else if let Some(sup) = order.downcast_ref::<base::SupportHold>() {
+        vec![&sup.target]
+    } else if let Some(sup) = order.downcast_ref::<base::SupportMove>() {
+        vec![&sup.src, &sup.dest]
+    } else if let Some(convoy) = order.downcast_ref::<base::Convoy>() {
+        vec![&convoy.src, &convoy.dest]
+    } else {
+        vec![]
+    }
+}
+
+/// Check that the map, units and orders are consistent with each other:
+/// every unit is on the map and has exactly one order,
+/// and every order refers only to provinces on the map.
+pub fn check_consistency(map: &Map, state: &MapState, orders: &Orders) -> Result<(), AdjudicationError> {
+    check_provinces(&map.provinces, state, orders)
+}
+
+fn check_provinces(provinces: &HashMap<ProvinceAbbr, Province>, state: &MapState, orders: &Orders) -> Result<(), AdjudicationError> {
+    // check in alphabetical order, so that the same error is reported for the same input
+    let mut units = state.units.keys().collect::<Vec<_>>();
+    units.sort();
+    for prov in units {
+        if !provinces.contains_key(prov) {
+            return Err(AdjudicationError::UnknownProvince(prov.to_string()));
+        }
+        if !orders.contains_key(prov) {
+            return Err(AdjudicationError::MissingOrder(prov.to_string()));
+        }
+    }
+
+    let mut orders = orders.iter().collect::<Vec<_>>();
+    orders.sort_by_key(|(prov, _)| *prov);
+    for (prov, order) in orders {
+        if !provinces.contains_key(prov) {
+            return Err(AdjudicationError::UnknownProvince(prov.to_string()));
+        }
+        if !state.units.contains_key(prov) {
+            return Err(AdjudicationError::NoUnit(prov.to_string()));
+        }
+        for target in order_targets(order.deref()) {
+            if !provinces.contains_key(target) {
+                return Err(AdjudicationError::UnknownTarget(prov.to_string(), target.to_string()));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Algorithm used to resolve the orders of a movement phase.
+/// Both give the same results.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Engine {
+    /// Repeatedly adjudicate every order until no more can be resolved,
+    /// then resolve circular movement and convoy paradoxes.
+    #[default]
+    Iterative,
+    /// Kruijswijk's recursive guess-and-backtrack algorithm.
+    GuessBacktrack,
+}
+
 /// Adjudicate a movement phase.
-pub fn adjudicate(map: &Map, state: &MapState, orders: &Orders) -> HashMap<String, bool> {
+///
+/// Fails if the input is inconsistent; see [`check_consistency`].
+pub fn adjudicate(map: &Map, rules: &RuleSet, state: &MapState, orders: &Orders) -> Result<HashMap<String, bool>, AdjudicationError> {
+    adjudicate_with(map, rules, state, orders, Engine::default())
+}
+
+/// Adjudicate a movement phase using the given engine.
+pub fn adjudicate_with(map: &Map, rules: &RuleSet, state: &MapState, orders: &Orders, engine: Engine) -> Result<HashMap<String, bool>, AdjudicationError> {
+    check_consistency(map, state, orders)?;
+    adjudicate_compiled(&CompiledMap::new(map), rules, state, orders, engine)
+}
+
+/// Adjudicate a movement phase on a map compiled beforehand,
+/// to avoid compiling the map again when adjudicating many positions.
+pub fn adjudicate_compiled(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &Orders, engine: Engine) -> Result<HashMap<String, bool>, AdjudicationError> {
+    check_provinces(&map.provinces, state, orders)?;
+    let orders = &IndexedOrders::new(orders);
+    Ok(match engine {
+        Engine::Iterative => adjudicate_tracked(map, rules, state, orders).0,
+        Engine::GuessBacktrack => kruijswijk::adjudicate(map, rules, state, orders),
+    })
+}
+
+/// Adjudicate a movement phase, explaining the result of each order.
+pub fn adjudicate_explained(map: &Map, rules: &RuleSet, state: &MapState, orders: &Orders) -> Result<HashMap<String, explain::OrderReport>, AdjudicationError> {
+    check_consistency(map, state, orders)?;
+    let map = &CompiledMap::new(map);
+    let orders = &IndexedOrders::new(orders);
+    let (order_status, resolutions) = adjudicate_tracked(map, rules, state, orders);
+    Ok(explain::explain(map, rules, state, orders, &order_status, &resolutions))
+}
+
+/// Adjudicate a movement phase, keeping track of how each order was resolved.
+fn adjudicate_tracked(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders) -> (HashMap<String, bool>, HashMap<String, explain::Resolution>) {
     let mut order_status: HashMap<String, bool> = HashMap::new();
+    let mut resolutions: HashMap<String, explain::Resolution> = HashMap::new();
+    let mut mark_resolved = |order_status: &HashMap<String, bool>, resolution: explain::Resolution| {
+        for prov in order_status.keys() {
+            resolutions.entry(prov.to_string()).or_insert(resolution);
+        }
+    };
+
     loop {
         if order_status.len() == orders.len() {
             break;
@@ -147,12 +353,13 @@ pub fn adjudicate(map: &Map, state: &MapState, orders: &Orders) -> HashMap<Strin
 
         let num_resolved = order_status.len();
 
-        for (prov, order) in orders.iter() {
+        for prov in orders.provinces().iter().copied() {
             if order_status.contains_key(prov) {
                 continue;
             }
+            let order = &orders[prov];
 
-            let deps = order.deps(&map, &state, &orders, prov);
+            let deps = order.deps(map, rules, state, orders, prov);
             let mut restricted_order_status = HashMap::new();
             for dep_prov in deps {
                 if order_status.contains_key(&dep_prov) {
@@ -161,7 +368,7 @@ pub fn adjudicate(map: &Map, state: &MapState, orders: &Orders) -> HashMap<Strin
                 }
             }
 
-            match order.adjudicate(&map, &state, &orders, prov, &restricted_order_status) {
+            match order.adjudicate(map, rules, state, orders, prov, &restricted_order_status) {
                 Some(status) => {
                     order_status.insert(prov.to_string(), status);
                 }
@@ -171,23 +378,27 @@ pub fn adjudicate(map: &Map, state: &MapState, orders: &Orders) -> HashMap<Strin
 
         if order_status.len() != num_resolved {
             // skip paradox step if an order was resolved
+            mark_resolved(&order_status, explain::Resolution::Direct);
             continue;
         }
 
         // paradoxes
     
-        paradox::handle_cycles(map, state, orders, &mut order_status);
+        paradox::handle_cycles(map, rules, state, orders, &mut order_status);
         if order_status.len() != num_resolved {
+            mark_resolved(&order_status, explain::Resolution::Cycle);
             continue;
         }
 
-        paradox::handle_convoy(map, state, orders, &mut order_status);
+        paradox::handle_convoy(map, rules, state, orders, &mut order_status);
         if order_status.len() != num_resolved {
+            mark_resolved(&order_status, explain::Resolution::ConvoyParadox);
             continue;
         }
 
         break;
     }
+    mark_resolved(&order_status, explain::Resolution::Direct);
 
-    order_status
+    (order_status, resolutions)
 }
@@ -0,0 +1,279 @@
+//! Standard text notation for orders, such as `A Par - Bur`, `F Nth C A Lon - Nwy`,
+//! `A Tyr S A Ven - Tri`, `A Lon - Bel via Convoy` or `F Spa(sc) H`.
+//!
+//! Provinces may be given by abbreviation or, if [`MapMeta`] is available, by full name or alias,
+//! in any case (see [`MapMeta::resolve_province`]). Coasts are written as `Spa(sc)` or `Spa/sc`.
+//! Unit types in front of supported and convoyed units are optional.
+
+use std::{error::Error, fmt::Display};
+
+use crate::{
+    base::{Convoy, Hold, Move, SupportHold, SupportMove},
+    core::Core,
+    utils::{split_coast, MapMeta},
+    FleetLoc, Map, Order, Unit,
+};
+
+/// The type of a unit, without nationality or location.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum UnitType {
+    Army,
+    Fleet,
+}
+
+impl UnitType {
+    pub fn of(unit: &Unit) -> Self {
+        match unit {
+            Unit::Army(_) => UnitType::Army,
+            Unit::Fleet(_, _) => UnitType::Fleet,
+        }
+    }
+
+    fn parse(token: &str) -> Option<Self> {
+        match token.to_lowercase().as_str() {
+            "a" | "army" => Some(UnitType::Army),
+            "f" | "fleet" => Some(UnitType::Fleet),
+            _ => None,
+        }
+    }
+}
+
+/// An order parsed from text.
+#[derive(Clone, Debug)]
+pub struct ParsedOrder {
+    /// The type of the ordered unit, if given.
+    pub unit: Option<UnitType>,
+    /// The location of the ordered unit.
+    pub loc: FleetLoc,
+    pub order: Box<dyn Order>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    Empty,
+    UnknownProvince(String),
+    /// The province does not have the given coast.
+    InvalidCoast(String, String),
+    /// A province was expected, but not found.
+    MissingProvince,
+    /// An unexpected word was found.
+    Unexpected(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "Empty order"),
+            ParseError::UnknownProvince(prov) => write!(f, "Unknown province {}", prov),
+            ParseError::InvalidCoast(prov, coast) => write!(f, "{} has no coast {}", prov, coast),
+            ParseError::MissingProvince => write!(f, "Expected a province"),
+            ParseError::Unexpected(word) => write!(f, "Unexpected {:?}", word),
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Kinds of keywords separating the parts of an order.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Keyword {
+    Move,
+    Hold,
+    Support,
+    Convoy,
+    Core,
+    Via,
+}
+
+fn keyword(token: &str) -> Option<Keyword> {
+    match token.to_lowercase().as_str() {
+        "-" | "->" | "=>" | "to" | "moves" => Some(Keyword::Move),
+        "h" | "hold" | "holds" => Some(Keyword::Hold),
+        "s" | "support" | "supports" => Some(Keyword::Support),
+        "c" | "convoy" | "convoys" => Some(Keyword::Convoy),
+        "core" => Some(Keyword::Core),
+        "via" => Some(Keyword::Via),
+        _ => None,
+    }
+}
+
+/// Resolve a location written in text.
+fn resolve_loc(map: &Map, meta: Option<&MapMeta>, text: &str) -> Result<FleetLoc, ParseError> {
+    let (abbr, coast) = match meta {
+        Some(meta) => meta.resolve_province(text),
+        None => {
+            let (prov, coast) = split_coast(text);
+            Some((prov.to_lowercase(), coast.to_lowercase()))
+        }
+    }
+    .filter(|(abbr, _)| map.provinces.contains_key(abbr))
+    .ok_or_else(|| ParseError::UnknownProvince(text.to_string()))?;
+
+    if !coast.is_empty() && !map.provinces[&abbr].coasts.contains(&coast) {
+        return Err(ParseError::InvalidCoast(abbr, coast));
+    }
+    Ok((abbr, coast))
+}
+
+/// A cursor over the words of an order.
+struct Tokens<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Tokens<'a> {
+    fn peek(&self) -> Option<&'a str> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<&'a str> {
+        let token = self.peek();
+        self.pos += 1;
+        token
+    }
+
+    fn peek_keyword(&self) -> Option<Keyword> {
+        self.peek().and_then(keyword)
+    }
+
+    /// Skip an optional unit type.
+    fn unit_type(&mut self) -> Option<UnitType> {
+        let unit = self.peek().and_then(UnitType::parse);
+        if unit.is_some() {
+            self.pos += 1;
+        }
+        unit
+    }
+
+    /// Read a location, which may consist of several words.
+    fn loc(&mut self, map: &Map, meta: Option<&MapMeta>) -> Result<FleetLoc, ParseError> {
+        let start = self.pos;
+        while self.peek().is_some() && self.peek_keyword().is_none() {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return Err(ParseError::MissingProvince);
+        }
+        resolve_loc(map, meta, &self.tokens[start..self.pos].join(" "))
+    }
+
+    fn expect(&mut self, kw: Keyword) -> Result<(), ParseError> {
+        match self.next() {
+            Some(token) if keyword(token) == Some(kw) => Ok(()),
+            Some(token) => Err(ParseError::Unexpected(token.to_string())),
+            None => Err(ParseError::MissingProvince),
+        }
+    }
+
+    fn end(&mut self) -> Result<(), ParseError> {
+        match self.next() {
+            None => Ok(()),
+            Some(token) => Err(ParseError::Unexpected(token.to_string())),
+        }
+    }
+}
+
+/// Parse an order written in standard notation.
+///
+/// `meta` is used to resolve full province names and aliases; without it, only abbreviations are accepted.
+pub fn parse_order(map: &Map, meta: Option<&MapMeta>, text: &str) -> Result<ParsedOrder, ParseError> {
+    let mut tokens = Tokens {
+        tokens: text.split_whitespace().collect(),
+        pos: 0,
+    };
+    if tokens.peek().is_none() {
+        return Err(ParseError::Empty);
+    }
+
+    let unit = tokens.unit_type();
+    let loc = tokens.loc(map, meta)?;
+
+    let order: Box<dyn Order> = match tokens.next().map(|t| (t, keyword(t))) {
+        None | Some((_, Some(Keyword::Hold))) => Box::new(Hold),
+        Some((_, Some(Keyword::Core))) => Box::new(Core),
+        Some((_, Some(Keyword::Move))) => {
+            let dest = tokens.loc(map, meta)?;
+            let via_convoy = tokens.peek_keyword() == Some(Keyword::Via);
+            if via_convoy {
+                tokens.next();
+                tokens.expect(Keyword::Convoy)?;
+            }
+            Box::new(Move { dest, via_convoy })
+        }
+        Some((_, Some(Keyword::Support))) => {
+            tokens.unit_type();
+            let (src, _) = tokens.loc(map, meta)?;
+            match tokens.peek_keyword() {
+                None | Some(Keyword::Hold) => {
+                    tokens.next();
+                    Box::new(SupportHold { target: src })
+                }
+                Some(Keyword::Move) => {
+                    tokens.next();
+                    let (dest, _) = tokens.loc(map, meta)?;
+                    Box::new(SupportMove { src, dest })
+                }
+                Some(_) => return Err(ParseError::Unexpected(tokens.peek().unwrap_or("").to_string())),
+            }
+        }
+        Some((_, Some(Keyword::Convoy))) => {
+            tokens.unit_type();
+            let (src, _) = tokens.loc(map, meta)?;
+            tokens.expect(Keyword::Move)?;
+            let (dest, _) = tokens.loc(map, meta)?;
+            Box::new(Convoy { src, dest })
+        }
+        Some((token, None | Some(Keyword::Via))) => return Err(ParseError::Unexpected(token.to_string())),
+    };
+    tokens.end()?;
+
+    Ok(ParsedOrder { unit, loc, order })
+}
+
+/// Format a province abbreviation, e.g. `Par`.
+fn format_prov(prov: &str) -> String {
+    let mut chars = prov.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// Format a location, e.g. `Spa(sc)`.
+pub fn format_loc(loc: &FleetLoc) -> String {
+    if loc.1.is_empty() {
+        format_prov(&loc.0)
+    } else {
+        format!("{}({})", format_prov(&loc.0), loc.1)
+    }
+}
+
+/// Format an order in standard notation, e.g. `A Par - Bur`.
+pub fn format_order(unit: &Unit, prov: &str, order: &dyn Order) -> String {
+    let unit_str = match unit {
+        Unit::Army(_) => format!("A {}", format_prov(prov)),
+        Unit::Fleet(_, coast) => format!("F {}", format_loc(&(prov.to_string(), coast.to_string()))),
+    };
+
+    let order_str = if order.is::<Hold>() {
+        "H".to_string()
+    } else if order.is::<Core>() {
+        "Core".to_string()
+    } else if let Some(mov) = order.downcast_ref::<Move>() {
+        if mov.via_convoy {
+            format!("- {} via Convoy", format_loc(&mov.dest))
+        } else {
+            format!("- {}", format_loc(&mov.dest))
+        }
+    } else if let Some(sup) = order.downcast_ref::<SupportHold>() {
+        format!("S {}", format_prov(&sup.target))
+    } else if let Some(sup) = order.downcast_ref::<SupportMove>() {
+        format!("S {} - {}", format_prov(&sup.src), format_prov(&sup.dest))
+    } else if let Some(convoy) = order.downcast_ref::<Convoy>() {
+        format!("C {} - {}", format_prov(&convoy.src), format_prov(&convoy.dest))
+    } else {
+        format!("{:?}", order)
+    };
+
+    format!("{} {}", unit_str, order_str)
+}
@@ -1,7 +1,8 @@
 
 use std::{hash::Hash, thread::current};
 
-use base::{compute_attack_strength, compute_defend_strength, compute_prevent_strength, is_path, Convoy, Move};
+use base::{compute_attack_strength, compute_defend_strength, compute_prevent_strength, is_path, Convoy, Move, SupportHold, SupportMove};
+use rules::ConvoyParadox;
 use frozenset::{Freeze, FrozenSet};
 
 use crate::*;
@@ -11,13 +12,13 @@ use crate::*;
 /// for which each move has greater attack strength
 /// than the prevent strength of all other units
 /// moving to the same province.
-fn get_cycle_at(map: &Map, state: &MapState, orders: &Orders, order_status: &mut HashMap<String, bool>, start: &str) -> Option<HashSet<String>> {
+fn get_cycle_at(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &mut HashMap<String, bool>, start: &str) -> Option<HashSet<String>> {
     let mut cycle = HashSet::new();
 
     let mut current_prov = start;
     loop {
         if !(orders.contains_key(current_prov) && orders[current_prov].is::<Move>() && order_status.get(current_prov).map(|x| *x) != Some(false) &&
-            is_path(map, state, orders, order_status, current_prov) == Some(true)) {
+            is_path(map, rules, state, orders, order_status, current_prov) == Some(true)) {
             return None
         }
 
@@ -26,15 +27,13 @@ fn get_cycle_at(map: &Map, state: &MapState, orders: &Orders, order_status: &mut
         let mov = orders[current_prov].downcast_ref::<Move>().unwrap();
 
         // if the attack might not succeed, invalid cycle
-        let attack_strength = compute_defend_strength(map, state, orders, order_status, current_prov);
+        let attack_strength = compute_defend_strength(map, rules, state, orders, order_status, current_prov);
         let mut strengths = vec![];
-        for (prov_it, order_it) in orders.iter() {
-            if prov_it == current_prov {
+        for prov_it in orders.moves_to(&mov.dest.0) {
+            if *prov_it == current_prov {
                 continue
             }
-            if Move::is_move_to(order_it.deref(), &mov.dest.0) {
-                strengths.push(compute_prevent_strength(map, state, orders, order_status, prov_it));
-            }
+            strengths.push(compute_prevent_strength(map, rules, state, orders, order_status, prov_it));
         }
 
         let mut definitely_wins = true;
@@ -64,16 +63,16 @@ fn get_cycle_at(map: &Map, state: &MapState, orders: &Orders, order_status: &mut
 
 /// Resolve all move orders in
 /// a cycle with success.
-pub fn handle_cycles(map: &Map, state: &MapState, orders: &Orders, order_status: &mut HashMap<String, bool>) {
+pub fn handle_cycles(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &mut HashMap<String, bool>) {
     loop {
         let num_resolved = order_status.len();
 
-        for (prov_it, order_it) in orders {
-            if order_status.get(prov_it) != None || !order_it.is::<Move>()  {
+        for prov_it in orders.provinces().iter().copied() {
+            if order_status.contains_key(prov_it) || !orders[prov_it].is::<Move>()  {
                 continue
             }
 
-            match get_cycle_at(map, state, orders, order_status, prov_it) {
+            match get_cycle_at(map, rules, state, orders, order_status, prov_it) {
                 Some(cycle) => {
                     for item in cycle {
                         order_status.insert(item, true);
@@ -90,7 +89,9 @@ pub fn handle_cycles(map: &Map, state: &MapState, orders: &Orders, order_status:
     }
 }
 
-fn get_component(map: &Map, state: &MapState, orders: &Orders, order_status: &mut HashMap<String, bool>, start: &str) -> FrozenSet<String> {
+/// Compute the component of the dependency graph containing `start`.
+/// Returns the convoy orders in the component, and all orders in the component.
+fn get_component(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &mut HashMap<String, bool>, start: &str) -> (FrozenSet<String>, HashSet<String>) {
     let mut component = HashSet::new();
     let mut visited = HashSet::new();
     let mut stack = vec![start.to_string()];
@@ -105,7 +106,7 @@ fn get_component(map: &Map, state: &MapState, orders: &Orders, order_status: &mu
             continue
         }
 
-        for dep in orders[&node].deps(map, state, orders, &node) {
+        for dep in orders[&node].deps(map, rules, state, orders, &node) {
             if !visited.contains(&dep) {
                 stack.push(dep);
             }
@@ -118,34 +119,54 @@ fn get_component(map: &Map, state: &MapState, orders: &Orders, order_status: &mu
         visited.insert(node);
     }
     
-    component.freeze()
+    (component.freeze(), visited)
 }
 
 /// Compute the component of unresolved moves
 /// with the minimum number of convoy orders,
 /// and set all convoy orders in that component to fail.
+/// Under [`ConvoyParadox::AllHold`](crate::rules::ConvoyParadox::AllHold),
+/// all moves, supports and convoys in the component fail instead.
 /// 
 /// If there are multiple components with the same
 /// minimal number of convoy moves, all convoy orders
 /// in all such components fail.
-pub fn handle_convoy(map: &Map, state: &MapState, orders: &Orders, order_status: &mut HashMap<String, bool>) {
-    let mut components: HashSet<FrozenSet<String>> = HashSet::new();
-    for (prov_it, order_it) in orders.iter() {
-        if order_it.is::<Convoy>() && order_status.get(prov_it) == None {
-            components.insert(get_component(map, state, orders, order_status, prov_it));
+pub fn handle_convoy(map: &CompiledMap, rules: &RuleSet, state: &MapState, orders: &IndexedOrders, order_status: &mut HashMap<String, bool>) {
+    // components in order of their first convoy, so that they are handled in the same order every time
+    let mut components: Vec<(FrozenSet<String>, HashSet<String>)> = vec![];
+    for prov_it in orders.provinces().iter().copied() {
+        if orders[prov_it].is::<Convoy>() && !order_status.contains_key(prov_it) {
+            let (convoys, members) = get_component(map, rules, state, orders, order_status, prov_it);
+            if !components.iter().any(|(other, _)| *other == convoys) {
+                components.push((convoys, members));
+            }
         }
     }
 
-    let min = match components.iter().map(|x| x.len()).min() {
+    let min = match components.iter().map(|(x, _)| x.len()).min() {
         None => return,
         Some(min) => min
     };
 
-    for component in components {
-        if component.len() == min {
-            for convoy_prov in component {
-                order_status.insert(convoy_prov, false);
+    for (component, members) in components {
+        if component.len() != min {
+            continue
+        }
+        match rules.convoy_paradox {
+            ConvoyParadox::Szykman => {
+                for convoy_prov in component {
+                    order_status.insert(convoy_prov, false);
+                }
+            }
+            ConvoyParadox::AllHold => {
+                for prov in members {
+                    let order = &orders[&prov];
+                    if order_status.get(&prov) == None
+                        && (order.is::<Move>() || order.is::<SupportHold>() || order.is::<SupportMove>() || order.is::<Convoy>()) {
+                        order_status.insert(prov, false);
+                    }
+                }
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,117 @@
+//! Adjudication of retreat phases.
+//!
+//! Use [`utils::apply_adjudication`](crate::utils::apply_adjudication) to compute the available
+//! retreats after a movement phase, and [`adjudicate_retreats`] to resolve the retreat orders.
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{base::Move, utils::RetreatOptions, FleetLoc, Map, MapState, Order, Unit};
+
+/// An order given to a dislodged unit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "dest", rename_all = "snake_case")]
+pub enum RetreatOrder {
+    Retreat(FleetLoc),
+    Disband,
+}
+
+impl RetreatOrder {
+    /// Interpret a movement-phase order as a retreat order.
+    /// Move orders become retreats; any other order disbands the unit.
+    pub fn from_order(order: &dyn Order) -> Self {
+        match order.downcast_ref::<Move>() {
+            Some(mov) => RetreatOrder::Retreat(mov.dest.clone()),
+            None => RetreatOrder::Disband,
+        }
+    }
+}
+
+/// The result of a retreat order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", content = "dest", rename_all = "snake_case")]
+pub enum RetreatOutcome {
+    /// The unit retreated to the given location.
+    Retreated(FleetLoc),
+    /// The unit was ordered to disband, or was not given an order.
+    Disbanded,
+    /// The unit was disbanded because another unit retreated to the same province.
+    Bounced,
+    /// The unit was disbanded because it could not retreat to the given location.
+    Invalid,
+}
+
+/// The location the unit described by `options` retreats to when ordered to `dest`,
+/// or `None` if it can't retreat there.
+///
+/// A fleet ordered to a province with coasts without giving one retreats to the only coast it can, if any.
+pub fn retreat_dest(map: &Map, options: &RetreatOptions, dest: &FleetLoc) -> Option<FleetLoc> {
+    match &options.src {
+        Unit::Army(_) => options.dest.contains(dest).then(|| dest.clone()),
+        // a fleet must retreat to a specific coast
+        Unit::Fleet(_, _) => {
+            let coasts = &map.provinces.get(&dest.0)?.coasts;
+            if dest.1.is_empty() && !coasts.is_empty() {
+                let mut reachable = options.dest.iter().filter(|(prov, coast)| *prov == dest.0 && coasts.contains(coast));
+                return match (reachable.next(), reachable.next()) {
+                    (Some(dest), None) => Some(dest.clone()),
+                    _ => None,
+                };
+            }
+            (options.dest.contains(dest) && (coasts.contains(&dest.1) || (dest.1.is_empty() && coasts.is_empty())))
+                .then(|| dest.clone())
+        }
+    }
+}
+
+/// Adjudicate a retreat phase.
+///
+/// `state` is the board after the movement phase, `retreats` the dislodged units
+/// as returned by [`apply_adjudication`](crate::utils::apply_adjudication),
+/// and `orders` the retreat orders, keyed by the province the unit was dislodged from.
+/// Units without an order are disbanded.
+///
+/// Returns the new board, and the outcome for each dislodged unit.
+pub fn adjudicate_retreats(
+    map: &Map,
+    state: &MapState,
+    retreats: &HashMap<String, RetreatOptions>,
+    orders: &HashMap<String, RetreatOrder>,
+) -> (MapState, HashMap<String, RetreatOutcome>) {
+    let mut outcomes = HashMap::new();
+    let mut destinations: HashMap<String, Vec<(&str, FleetLoc)>> = HashMap::new();
+
+    for (prov, options) in retreats.iter() {
+        match orders.get(prov) {
+            Some(RetreatOrder::Retreat(dest)) => match retreat_dest(map, options, dest) {
+                Some(dest) => destinations.entry(dest.0.clone()).or_default().push((prov.as_str(), dest)),
+                None => {
+                    outcomes.insert(prov.to_string(), RetreatOutcome::Invalid);
+                }
+            },
+            Some(RetreatOrder::Disband) | None => {
+                outcomes.insert(prov.to_string(), RetreatOutcome::Disbanded);
+            }
+        }
+    }
+
+    let mut new_state = state.clone();
+    for (dest_prov, srcs) in destinations {
+        if srcs.len() != 1 {
+            for (src, _) in srcs {
+                outcomes.insert(src.to_string(), RetreatOutcome::Bounced);
+            }
+            continue;
+        }
+
+        let (src, dest) = srcs.into_iter().next().unwrap();
+        new_state.units.insert(dest_prov, match &retreats[src].src {
+            Unit::Army(natl) => Unit::Army(natl.clone()),
+            Unit::Fleet(natl, _) => Unit::Fleet(natl.clone(), dest.1.clone()),
+        });
+        outcomes.insert(src.to_string(), RetreatOutcome::Retreated(dest));
+    }
+
+    (new_state, outcomes)
+}
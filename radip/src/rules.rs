@@ -0,0 +1,66 @@
+//! Rule options for the adjudicator.
+//!
+//! The rules of Diplomacy are ambiguous in a few places; section 4 of the
+//! [DATC](https://webdiplomacy.net/doc/DATC_v3_0.html#4) lists the possible interpretations.
+//! A [`RuleSet`] selects one interpretation for each of the supported issues.
+//! The default is the interpretation preferred by the DATC.
+
+use serde::{Deserialize, Serialize};
+
+use crate::utils::MapMeta;
+
+/// How paradoxes caused by convoys are resolved (DATC 4.A.2).
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvoyParadox {
+    /// The convoys involved in the paradox fail, so the convoyed armies do not move.
+    #[default]
+    Szykman,
+    /// All orders involved in the paradox fail.
+    AllHold,
+}
+
+/// When an army that is adjacent to its destination moves by convoy (DATC 4.A.3).
+///
+/// Armies ordered via convoy always move by convoy, whatever the rule.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConvoyIntent {
+    /// Whenever there is a successful convoy route.
+    #[default]
+    AnyRoute,
+    /// Only if a fleet of the same power is ordered to convoy it.
+    OwnFleet,
+    /// Never; the army moves over land unless ordered via convoy.
+    Never,
+}
+
+/// A choice of rule interpretations.
+///
+/// Variants may specify their rule set under the `rules` key of [`MapMeta::data`];
+/// see [`RuleSet::from_meta`].
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RuleSet {
+    pub convoy_paradox: ConvoyParadox,
+    pub convoy_intent: ConvoyIntent,
+
+    /// Whether fleets in coastal provinces may convoy.
+    pub coastal_convoys: bool,
+
+    /// Whether a fleet supporting a move to a province with coasts must be able
+    /// to reach the coast the supported unit moves to (DATC 4.B.4).
+    /// By default, it suffices to reach any coast of the province.
+    pub support_requires_coast: bool,
+}
+
+impl RuleSet {
+    /// The rule set of a variant, or the default rule set if the variant doesn't specify one.
+    /// Options missing from the variant's rule set take their default value.
+    pub fn from_meta(meta: &MapMeta) -> Result<Self, serde_json::Error> {
+        match meta.data.get("rules") {
+            Some(rules) => serde_json::from_value(rules.clone()),
+            None => Ok(Self::default()),
+        }
+    }
+}
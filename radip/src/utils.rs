@@ -1,10 +1,10 @@
 //! Auxiliary functionality not related to adjudicating a movement phase.
 
-use std::{cmp::min, collections::{HashMap, HashSet}};
+use std::{cmp::{min, Reverse}, collections::{HashMap, HashSet}};
 
 use serde::{Deserialize, Serialize};
 
-use crate::{base::{self, Move}, Map, MapState, Orders, ProvinceAbbr, Unit};
+use crate::{base::{self, Move}, check_consistency, graph::{self, Movement}, AdjudicationError, CompiledMap, IndexedOrders, Map, MapState, Orders, ProvinceAbbr, RuleSet, Unit};
 
 /// Metadata associated to a province.
 #[derive(Clone, Serialize, Deserialize)]
@@ -12,6 +12,14 @@ pub struct ProvinceMeta {
     pub name: String,
     pub is_sc: bool,
     pub home_sc: String,
+
+    /// Other names players may use for the province.
+    #[serde(default)]
+    pub aliases: Vec<String>,
+
+    /// Display name of each coast, e.g. `North Coast` for `nc`.
+    #[serde(default)]
+    pub coasts: HashMap<String, String>,
 }
 
 /// Metadata associated to a map.
@@ -29,6 +37,62 @@ pub struct MapMeta {
     pub data: HashMap<String, serde_json::Value>,
 }
 
+impl MapMeta {
+    /// Resolve a location written by a player to a province abbreviation and coast.
+    ///
+    /// The province may be given by abbreviation, full name or alias, and the coast, if any,
+    /// by abbreviation or display name, as in `StP/nc`, `St Petersburg (North Coast)` or `stp nc`.
+    /// Case and repeated whitespace are ignored.
+    /// The coast is returned in lowercase, but is only checked against the coast names of the province;
+    /// callers should check that the map has the coast.
+    pub fn resolve_province(&self, text: &str) -> Option<(ProvinceAbbr, String)> {
+        let text = text.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase();
+
+        let (prov, coast) = split_coast(&text);
+        if let Some((abbr, meta)) = self.find_province(prov) {
+            let coast = meta.coasts.iter()
+                .find(|(_, name)| name.to_lowercase() == coast)
+                .map(|(abbr, _)| abbr.to_string())
+                .unwrap_or_else(|| coast.to_string());
+            return Some((abbr.to_string(), coast));
+        }
+
+        // the coast may follow the province after a space
+        let mut words = text.match_indices(' ').map(|(i, _)| i).collect::<Vec<_>>();
+        words.reverse();
+        words.into_iter().find_map(|i| {
+            let (abbr, meta) = self.find_province(&text[..i])?;
+            let coast = &text[i + 1..];
+            meta.coasts.iter()
+                .find(|(abbr, name)| *abbr == coast || name.to_lowercase() == coast)
+                .map(|(coast, _)| (abbr.to_string(), coast.to_string()))
+        })
+    }
+
+    /// Find a province by lowercase abbreviation, name or alias.
+    fn find_province(&self, name: &str) -> Option<(&ProvinceAbbr, &ProvinceMeta)> {
+        self.provinces.get_key_value(name).or_else(|| {
+            // the first matching abbreviation, in case several provinces share a name
+            self.provinces.iter()
+                .filter(|(_, meta)| {
+                    meta.name.to_lowercase() == name || meta.aliases.iter().any(|alias| alias.to_lowercase() == name)
+                })
+                .min_by_key(|(abbr, _)| *abbr)
+        })
+    }
+}
+
+/// Split a location into province and coast, e.g. `Spa(sc)` or `Spa/sc` into `Spa` and `sc`.
+pub(crate) fn split_coast(text: &str) -> (&str, &str) {
+    if let (Some(i), true) = (text.find('('), text.ends_with(')')) {
+        return (text[..i].trim(), text[i + 1..text.len() - 1].trim());
+    }
+    if let Some(i) = text.rfind('/') {
+        return (text[..i].trim(), text[i + 1..].trim());
+    }
+    (text, "")
+}
+
 #[derive(Clone, Serialize, Deserialize)]
 pub struct PowerMeta {
     /// Full name; e.g. England, not ENG.
@@ -38,7 +102,7 @@ pub struct PowerMeta {
     pub sc_color: String,
 }
 
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct RetreatOptions {
     /// The retreating unit in its previous location.
     pub src: Unit,
@@ -48,10 +112,20 @@ pub struct RetreatOptions {
 }
 
 /// Update the game board based on adjudication results.
-pub fn apply_adjudication(map: &Map, state: &MapState, orders: &Orders, order_status: &HashMap<String, bool>) -> (MapState, HashMap<String, RetreatOptions>) {
+///
+/// Fails if the input is inconsistent (see [`check_consistency`]),
+/// or if `order_status` contains a province without an order.
+pub fn apply_adjudication(map: &Map, rules: &RuleSet, state: &MapState, orders: &Orders, order_status: &HashMap<String, bool>) -> Result<(MapState, HashMap<String, RetreatOptions>), AdjudicationError> {
+    check_consistency(map, state, orders)?;
+    if let Some(prov) = order_status.keys().filter(|prov| !orders.contains_key(*prov)).min() {
+        return Err(AdjudicationError::MissingOrder(prov.to_string()));
+    }
+
     let mut contested = HashSet::new();
+    let compiled = CompiledMap::new(map);
+    let indexed = IndexedOrders::new(orders);
     for (prov, order) in orders.iter() {
-        if order.is::<Move>() && base::compute_prevent_strength(map, state, orders, order_status, prov).min != 0 {
+        if order.is::<Move>() && base::compute_prevent_strength(&compiled, rules, state, &indexed, order_status, prov).min != 0 {
             let mov = order.downcast_ref::<Move>().unwrap();
             contested.insert(mov.dest.0.to_string());
         }
@@ -63,7 +137,7 @@ pub fn apply_adjudication(map: &Map, state: &MapState, orders: &Orders, order_st
     };
     for (prov, status) in order_status.iter() {
         if !(*status && orders[prov].is::<Move>()) {
-            new_state.units.insert(prov.clone(), state.units.get(prov).expect("no unit, yet there exists an order").clone());
+            new_state.units.insert(prov.clone(), state.units[prov].clone());
         }
     }
 
@@ -78,36 +152,40 @@ pub fn apply_adjudication(map: &Map, state: &MapState, orders: &Orders, order_st
                 });
             }
 
-            let unit = state.units.get(prov).expect("No unit, yet there exists an order");
+            let unit = &state.units[prov];
             new_state.units.insert(mov.dest.0.clone(), match unit {
                 Unit::Army(natl) => Unit::Army(natl.clone()),
-                Unit::Fleet(natl, _) => Unit::Fleet(natl.clone(), mov.dest.1.clone())
+                Unit::Fleet(natl, coast) => Unit::Fleet(natl.clone(), base::infer_coast(&compiled, prov, coast, &mov.dest).to_string())
             });
         }
     }
 
+    // a dislodged unit can't retreat to where the attacker came from, unless the attacker was convoyed
+    let attacked_from = |prov: &str, src_prov: &str| {
+        orders.get(prov).and_then(|order| order.downcast_ref::<Move>())
+            .is_some_and(|mov| mov.dest.0 == src_prov && order_status.get(prov) == Some(&true))
+            && base::is_convoy_path(&compiled, rules, state, &indexed, order_status, prov) != Some(true)
+    };
+
     for (src_prov, retreat) in retreats.iter_mut() {
-        match &retreat.src {
-            Unit::Army(natl) => {
-                for (src, dest) in map.army_adj.iter() {
-                    if src == src_prov && !contested.contains(dest) && !new_state.units.contains_key(dest) 
-                        && !(orders.contains_key(dest) && orders[dest].is::<Move>() && order_status.get(dest) == Some(&true) && orders[dest].downcast_ref::<Move>().unwrap().dest.0 == *src_prov) {
-                        retreat.dest.insert((dest.to_string(), "".to_string()));
-                    }
-                }
-            },
-            Unit::Fleet(natl, src_coast) => {
-                for (src, dest) in map.fleet_adj.iter() {
-                    if src.0 == *src_prov && src.1 == *src_coast && !contested.contains(&dest.0) && !new_state.units.contains_key(&dest.0) 
-                    && !(orders.contains_key(&dest.0) && orders[&dest.0].is::<Move>() && order_status.get(&dest.0) == Some(&true) && orders[&dest.0].downcast_ref::<Move>().unwrap().dest.0 == *src_prov) {
-                        retreat.dest.insert((dest.0.to_string(),dest.1.to_string()));
-                    }
-                }
+        let dests = match &retreat.src {
+            Unit::Army(_) => compiled.province_id(src_prov).iter()
+                .flat_map(|prov| compiled.army_neighbours(*prov))
+                .map(|dest| (compiled.province_name(*dest), ""))
+                .collect::<Vec<_>>(),
+            Unit::Fleet(_, src_coast) => compiled.loc_id(src_prov, src_coast).iter()
+                .flat_map(|loc| compiled.fleet_neighbours(*loc))
+                .map(|dest| compiled.loc(*dest))
+                .collect(),
+        };
+        for (dest, dest_coast) in dests {
+            if !contested.contains(dest) && !new_state.units.contains_key(dest) && !attacked_from(dest, src_prov) {
+                retreat.dest.insert((dest.to_string(), dest_coast.to_string()));
             }
         }
     }
 
-    (new_state, retreats)
+    Ok((new_state, retreats))
 }
 
 
@@ -121,28 +199,55 @@ pub fn count_supply(state: &MapState, power: &str) -> usize {
     return state.ownership.values().filter(|u| *u == power).count();
 }
 
-/// Decides which unit to disband in the case of civil disorder.
-pub fn disband_cd<'a>(map: &Map, state: &MapState, home: impl Iterator<Item=&'a str>, power: &str) -> Option<String> {
-    let mut unvisited = map.provinces.keys().map(|c| c.as_str()).collect::<HashSet<_>>();
-    let mut dist: HashMap<&str, u32> = HashMap::from_iter(unvisited.iter().map(|c| (*c, u32::MAX)));
-    for prov in home {
-        dist.insert(prov, 0);
-    }
-    loop {
-        let min = match unvisited.iter().map(|c| dist[c]).min() {
-            Some(min) => min,
-            None => break
-        };
-        let node = unvisited.iter().find(|c| dist[*c] == min).map(|c| *c).unwrap();
-
-        for neighbor in map.army_adj.iter().filter(|(a, _)| a == node).map(|(_, dest)| dest.as_str()).chain(
-            map.fleet_adj.iter().filter(|(a, _)| a.0 == node).map(|(_, dest)| dest.0.as_str())) {
-            dist.insert(neighbor, std::cmp::min(dist[neighbor], min + 1));
-        }
-    }
+/// The distance of each unit of `power` from home, as used to decide civil disorder disbands.
+///
+/// Distance is the least number of moves needed to reach one of the power's home supply centers
+/// that it still owns, or any of its home supply centers if it owns none of them.
+/// Armies may move through any province, land or sea, as if convoyed;
+/// fleets may only make fleet moves, starting from their coast.
+/// Units that can't reach home have no distance.
+pub fn cd_distances(map: &Map, meta: &MapMeta, state: &MapState, power: &str) -> HashMap<ProvinceAbbr, Option<u32>> {
+    let home = meta.provinces.iter()
+        .filter(|(_, p)| p.home_sc == power)
+        .map(|(prov, _)| prov.as_str())
+        .collect::<Vec<_>>();
+    let owned = home.iter()
+        .copied()
+        .filter(|prov| state.ownership.get(*prov).is_some_and(|owner| owner == power))
+        .collect::<Vec<_>>();
+    let home = if owned.is_empty() { home } else { owned };
+
+    let map = CompiledMap::new(map);
+    let army = graph::distances(&map, Movement::Any, home.iter().copied());
+    // fleets reach a home center on any of its coasts
+    let home_locs = home.iter().flat_map(|prov| {
+        let coasts: Vec<&str> = map.provinces.get(*prov).map(|p| p.coasts.iter().map(String::as_str).collect()).unwrap_or_default();
+        if coasts.is_empty() { vec![(*prov, "")] } else { coasts.into_iter().map(|coast| (*prov, coast)).collect() }
+    });
+    let fleet = graph::fleet_distances(&map, home_locs);
 
-    let max: u32 = state.units.iter().filter(|(_, u)| &u.nationality() == power).map(|(prov, _)| dist[prov.as_str()]).min()?;
     state.units.iter()
-        .filter(|(prov, u)| &u.nationality() == power && dist[prov.as_str()] == max)
-        .map(|(prov, _)| prov).min().map(|c| c.clone())
-}
\ No newline at end of file
+        .filter(|(_, unit)| unit.nationality() == power)
+        .map(|(prov, unit)| {
+            let dist = match unit {
+                Unit::Army(_) => army.get(prov).copied(),
+                Unit::Fleet(_, coast) => fleet.get(&(prov.to_string(), coast.to_string())).copied(),
+            };
+            (prov.to_string(), dist)
+        })
+        .collect()
+}
+
+/// Decides which unit to disband in the case of civil disorder.
+///
+/// The unit farthest from home (see [`cd_distances`]) is disbanded, and units that can't reach home are farthest.
+/// Ties are broken by disbanding fleets before armies, and then in alphabetical order of province name.
+pub fn disband_cd(map: &Map, meta: &MapMeta, state: &MapState, power: &str) -> Option<String> {
+    let name = |prov: &str| meta.provinces.get(prov).map(|p| p.name.to_string()).unwrap_or_else(|| prov.to_string());
+    cd_distances(map, meta, state, power).into_iter()
+        .min_by_key(|(prov, dist)| {
+            let is_army = matches!(state.units[prov], Unit::Army(_));
+            (Reverse(dist.unwrap_or(u32::MAX)), is_army, name(prov), prov.to_string())
+        })
+        .map(|(prov, _)| prov)
+}
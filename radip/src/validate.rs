@@ -0,0 +1,180 @@
+//! Validation of movement phase orders.
+//!
+//! The adjudicator accepts any order, and simply fails orders that can't succeed.
+//! [`validate_order`] can be used to reject such orders when they are given,
+//! with a reason for the player.
+
+use std::{
+    collections::{HashSet, VecDeque},
+    error::Error,
+    fmt::Display,
+};
+
+use crate::{
+    base::{infer_coast, unit_can_reach, Convoy, Move, SupportHold, SupportMove},
+    CompiledMap, MapState, Order, RuleSet, Unit,
+};
+
+/// Reason an order is illegal.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum OrderError {
+    UnknownProvince(String),
+    /// There is no unit in the given province.
+    NoUnit(String),
+    /// The unit can't reach the given province.
+    Unreachable(String),
+    /// The order can't be given to this type of unit.
+    WrongUnitType,
+    /// The given province doesn't have the given coast,
+    /// or a coast must be specified because the unit could reach several.
+    InvalidCoast(String, String),
+    /// Only fleets at sea can convoy.
+    NotAtSea(String),
+}
+
+impl Display for OrderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrderError::UnknownProvince(prov) => write!(f, "Unknown province {}", prov),
+            OrderError::NoUnit(prov) => write!(f, "There is no unit at {}", prov),
+            OrderError::Unreachable(prov) => write!(f, "Unit can't reach {}", prov),
+            OrderError::WrongUnitType => write!(f, "Order can't be given to this type of unit"),
+            OrderError::InvalidCoast(prov, coast) if coast.is_empty() => write!(f, "A coast of {} must be given", prov),
+            OrderError::InvalidCoast(prov, coast) => write!(f, "{} has no coast {}", prov, coast),
+            OrderError::NotAtSea(prov) => write!(f, "Fleet at {} is not at sea", prov),
+        }
+    }
+}
+
+impl Error for OrderError {}
+
+fn check_province(map: &CompiledMap, prov: &str) -> Result<(), OrderError> {
+    if map.provinces.contains_key(prov) {
+        Ok(())
+    } else {
+        Err(OrderError::UnknownProvince(prov.to_string()))
+    }
+}
+
+fn unit_at<'a>(state: &'a MapState, prov: &str) -> Result<&'a Unit, OrderError> {
+    state.units.get(prov).ok_or_else(|| OrderError::NoUnit(prov.to_string()))
+}
+
+/// The fleets able to convoy that are connected to `src` through other such fleets,
+/// and so could take part in convoying an army from `src`.
+pub(crate) fn convoying_fleets(map: &CompiledMap, rules: &RuleSet, state: &MapState, src: &str) -> HashSet<String> {
+    let is_fleet_at_sea = |prov: &str| {
+        matches!(state.units.get(prov), Some(Unit::Fleet(_, _)))
+            && map.provinces.get(prov).map(|p| p.is_sea || rules.coastal_convoys).unwrap_or(false)
+    };
+
+    let mut visited = HashSet::new();
+    let mut queue = VecDeque::from_iter(map.loc_id(src, ""));
+    while let Some(node) = queue.pop_front() {
+        for &next in map.convoy_neighbours(node) {
+            let (b, b_coast) = map.loc(next);
+            if b_coast.is_empty() && is_fleet_at_sea(b) && visited.insert(b.to_string()) {
+                queue.push_back(next);
+            }
+        }
+    }
+    visited
+}
+
+/// Whether an army in `src` could be convoyed to `dest` by the fleets currently on the board.
+pub fn has_convoy_route(map: &CompiledMap, rules: &RuleSet, state: &MapState, src: &str, dest: &str) -> bool {
+    if src == dest
+        || map.provinces.get(src).map(|p| p.is_sea).unwrap_or(true)
+        || map.provinces.get(dest).map(|p| p.is_sea).unwrap_or(true) {
+        return false;
+    }
+
+    convoying_fleets(map, rules, state, src).iter()
+        .any(|fleet| map.fleet_adjacent(fleet, "", dest, ""))
+}
+
+fn validate_move(map: &CompiledMap, rules: &RuleSet, state: &MapState, prov: &str, unit: &Unit, mov: &Move) -> Result<(), OrderError> {
+    let dest = &mov.dest.0;
+    check_province(map, dest)?;
+    if dest == prov {
+        return Err(OrderError::Unreachable(dest.to_string()));
+    }
+
+    match unit {
+        Unit::Army(_) => {
+            if (mov.via_convoy || !map.army_adjacent(prov, dest)) && !has_convoy_route(map, rules, state, prov, dest) {
+                return Err(OrderError::Unreachable(dest.to_string()));
+            }
+        }
+        Unit::Fleet(_, src_coast) => {
+            if mov.via_convoy {
+                return Err(OrderError::WrongUnitType);
+            }
+            let coasts = &map.provinces[dest].coasts;
+            let dest_coast = infer_coast(map, prov, src_coast, &mov.dest);
+            if dest_coast.is_empty() && !coasts.is_empty() && !coasts.iter().any(|coast| map.fleet_adjacent(prov, src_coast, dest, coast)) {
+                return Err(OrderError::Unreachable(dest.to_string()));
+            }
+            if !(coasts.contains(dest_coast) || (dest_coast.is_empty() && coasts.is_empty())) {
+                return Err(OrderError::InvalidCoast(dest.to_string(), dest_coast.to_string()));
+            }
+            if !map.fleet_adjacent(prov, src_coast, dest, dest_coast) {
+                return Err(OrderError::Unreachable(dest.to_string()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Check whether the given order is legal for the unit in `prov`.
+///
+/// Orders of types other than those in [`base`](crate::base) are only checked for the
+/// existence of the ordered unit.
+///
+/// The map is taken compiled, so that callers validating many orders compile it only once.
+pub fn validate_order(map: &CompiledMap, rules: &RuleSet, state: &MapState, prov: &str, order: &dyn Order) -> Result<(), OrderError> {
+    check_province(map, prov)?;
+    let unit = unit_at(state, prov)?;
+
+    if let Some(mov) = order.downcast_ref::<Move>() {
+        validate_move(map, rules, state, prov, unit, mov)?;
+    } else if let Some(sup) = order.downcast_ref::<SupportHold>() {
+        check_province(map, &sup.target)?;
+        unit_at(state, &sup.target)?;
+        if sup.target == prov || !unit_can_reach(map, state, prov, &sup.target) {
+            return Err(OrderError::Unreachable(sup.target.to_string()));
+        }
+    } else if let Some(sup) = order.downcast_ref::<SupportMove>() {
+        check_province(map, &sup.src)?;
+        check_province(map, &sup.dest)?;
+        let supported = unit_at(state, &sup.src)?;
+        if sup.dest == prov || sup.src == prov || !unit_can_reach(map, state, prov, &sup.dest) {
+            return Err(OrderError::Unreachable(sup.dest.to_string()));
+        }
+        let supported_move = Move { dest: (sup.dest.to_string(), "".to_string()), via_convoy: false };
+        let supported_reach = match supported {
+            Unit::Army(_) => validate_move(map, rules, state, &sup.src, supported, &supported_move),
+            Unit::Fleet(_, _) => if unit_can_reach(map, state, &sup.src, &sup.dest) { Ok(()) } else { Err(OrderError::Unreachable(sup.dest.to_string())) },
+        };
+        supported_reach?;
+    } else if let Some(convoy) = order.downcast_ref::<Convoy>() {
+        check_province(map, &convoy.src)?;
+        check_province(map, &convoy.dest)?;
+        if !matches!(unit, Unit::Fleet(_, _)) {
+            return Err(OrderError::WrongUnitType);
+        }
+        if !(map.provinces[prov].is_sea || rules.coastal_convoys) {
+            return Err(OrderError::NotAtSea(prov.to_string()));
+        }
+        if !matches!(unit_at(state, &convoy.src)?, Unit::Army(_)) {
+            return Err(OrderError::WrongUnitType);
+        }
+        // the fleet must be part of a chain of fleets reaching the army
+        if !convoying_fleets(map, rules, state, &convoy.src).contains(prov)
+            || !has_convoy_route(map, rules, state, &convoy.src, &convoy.dest) {
+            return Err(OrderError::Unreachable(convoy.dest.to_string()));
+        }
+    }
+
+    Ok(())
+}
@@ -6,7 +6,14 @@ export function unitNatl(unit: Unit) : string {
 }
 export interface Province {
     is_sea: boolean,
-    coasts: string[]
+    coasts: string[],
+    canal?: Canal,
+    impassable?: boolean,
+}
+
+export interface Canal {
+    connects: Array<[[string, string], [string, string]]>,
+    convoys: boolean,
 }
 
 export interface MapState {
@@ -17,7 +24,7 @@ export interface MapState {
 export type FleetLoc = [string, string];
 export type ArmyLoc = string;
 
-export type MoveOrder = { type: "move", dest: FleetLoc };
+export type MoveOrder = { type: "move", dest: FleetLoc, via_convoy?: boolean };
 export type Order = MoveOrder
     | { type : "hold" }
     | { type : "convoy", src: string, dest: string } 
@@ -37,6 +44,7 @@ export interface AdjData {
     provinces: Record<string, Province>,
     fleet_adj: Array<[[string, string], [string, string]]>
     army_adj: Array<[string, string]>,
+    one_way?: Array<[string, string]>,
 }
 
 export interface PowerMeta {
@@ -134,7 +142,8 @@ export type PressType = "full" | "rulebook" | "public" | "gunboat";
 export type Build = 
     { type : "disband" } | 
     { type : "fleet", coast: string } |
-    { type : "army" };
+    { type : "army" } |
+    { type : "waive" };
 export type Builds = Record<string, Build>;
 
 export interface MvmtInfo {
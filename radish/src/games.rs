@@ -1,6 +1,6 @@
-use std::{collections::{HashMap, HashSet}, future::pending, hash::Hash, io::{Cursor, Read}, time::{SystemTime, UNIX_EPOCH}};
+use std::{collections::{HashMap, HashSet}, future::pending, hash::Hash, io::{Cursor, Read}, ops::Deref, time::{SystemTime, UNIX_EPOCH}};
 use rand::prelude::*;
-use radip::{adjudicate, base::{self, Hold, Move}, utils::{apply_adjudication, count_supply, count_units, disband_cd, MapMeta, RetreatOptions}, Map, MapState, Orders, ProvinceAbbr, Unit};
+use radip::{adjudicate, adjustment::{adjudicate_adjustments, validate_builds, Builds}, base::{self, Hold, Move}, game::{update_ownership, GamePhase}, retreat::{adjudicate_retreats, retreat_dest, RetreatOrder}, utils::{apply_adjudication, MapMeta, RetreatOptions}, legal_orders, validate_order, AdjudicationError, CompiledMap, Map, MapState, Order, Orders, ProvinceAbbr, RuleSet, Unit};
 use rocket::{build, form::Form, fs::{NamedFile, TempFile}, futures::{SinkExt, StreamExt}, http::{CookieJar, Status}, response::{content::RawHtml, Redirect}, serde::{json::Json, Deserialize, Serialize}, tokio::{io::AsyncReadExt, select, sync::broadcast, time::{Duration, Instant}}, State};
 use tokio::{sync::broadcast::error::RecvError, time};
 use ws::{stream::DuplexStream, Message};
@@ -59,11 +59,23 @@ pub async fn create_game_submit(cookies: &CookieJar<'_>, state: &State<AppState>
         .map_err(|e| Redirect::to(format!("/error?msg=Invalid+variant+file&details={}", encode_error(e))))?)
         .map_err(|e| Redirect::to(format!("/error?msg=Invalid+variant+file&details={}", encode_error(e))))?;
 
+        let problems = adj.validate().into_iter().chain(meta.validate(&adj))
+            .map(|problem| problem.to_string())
+            .collect::<Vec<_>>();
+        if !problems.is_empty() {
+            return Err(Redirect::to(format!("/error?msg=Invalid+variant+map&details={}", encode_error(problems.join("; ")))));
+        }
+
+        let rules = RuleSet::from_meta(&meta)
+            .map_err(|e| Redirect::to(format!("/error?msg=Invalid+variant+rules&details={}", encode_error(e))))?;
+
         state.variants.insert(variant_id.to_string(), Variant {
+            compiled: CompiledMap::new(&adj),
             adj: adj,
             svg: map,
             meta: meta.clone(),
             pos: pos,
+            rules: rules,
         });
     }
 
@@ -150,72 +162,12 @@ pub struct GameMeta {
     pub variant: String,
 }
 
-#[derive(Clone, Serialize, Debug, Deserialize, Copy, PartialEq, Eq, Hash)]
-#[serde(rename_all="snake_case")]
-pub enum GamePhase {
-    Spring,
-    SpringRetreat,
-    Fall,
-    FallRetreat,
-    Winter
-}
-impl GamePhase {
-    pub fn is_move(self) -> bool {
-        match self {
-            GamePhase::Fall | GamePhase::Spring => true,
-            GamePhase::SpringRetreat | GamePhase::FallRetreat => false,
-            GamePhase::Winter => false,
-        }
-    }
-    pub fn is_retreat(self) -> bool {
-        match self {
-            GamePhase::Fall | GamePhase::Spring => false,
-            GamePhase::SpringRetreat | GamePhase::FallRetreat => true,
-            GamePhase::Winter => false,
-        }
-    }
-    pub fn is_build(self) -> bool {
-        match self {
-            GamePhase::Fall | GamePhase::Spring => false,
-            GamePhase::SpringRetreat | GamePhase::FallRetreat => false,
-            GamePhase::Winter => true,
-        }
-    }
-
-    pub fn next(self, year: u8) -> (Self, u8) {
-        match self {
-            GamePhase::Spring => (GamePhase::SpringRetreat, year),
-            GamePhase::SpringRetreat => (GamePhase::Fall, year),
-            GamePhase::Fall => (GamePhase::FallRetreat, year),
-            GamePhase::FallRetreat => (GamePhase::Winter, year),
-            GamePhase::Winter => (GamePhase::Spring, year+1)
-        }
-    }
-    pub fn mvmt(self) -> Self {
-        match self {
-            GamePhase::FallRetreat | GamePhase::Fall => GamePhase::Fall,
-            GamePhase::SpringRetreat | GamePhase::Spring => GamePhase::Spring,
-            GamePhase::Winter => GamePhase::Winter
-        }
-    }
-}
-
 #[derive(Clone)]
 pub struct MvmtPhaseInfo {
     order_status: HashMap<String, bool>,
     retreats: HashMap<String, RetreatOptions>
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-#[serde(tag = "type", content="coast", rename_all="snake_case")]
-pub enum Build {
-    Disband,
-    Fleet(String),
-    Army
-}
-
-type Builds = HashMap<String, Build>;
-
 pub struct GameState {
     /// For each phase, this exists on phase start.
     pub states: HashMap<(u8, GamePhase), MapState>,
@@ -320,6 +272,37 @@ pub fn game_meta(state: &State<AppState>, id: &str) -> Result<Json<GameMeta>, St
     Ok(Json(game.meta.clone()))
 }
 
+/// The legal orders for the unit in `prov`, in the current phase of the game.
+#[get("/games/<id>/legal/<prov>")]
+pub fn game_legal_orders(state: &State<AppState>, id: &str, prov: &str) -> Result<Json<Vec<Box<dyn Order>>>, Status> {
+    let game = state.games.get(id).ok_or(Status::NotFound)?;
+    let gstate = game.state.as_ref().ok_or(Status::NotFound)?;
+    if !gstate.phase.is_move() {
+        return Ok(Json(vec![]));
+    }
+    let variant = state.variants.get(&game.meta.variant).ok_or(Status::NotFound)?;
+    Ok(Json(legal_orders(&variant.compiled, &variant.rules, gstate.current_state(), prov)))
+}
+
+/// Adjudicate a movement phase. Orders rejected by the adjudicator
+/// are replaced by holds, or removed if there is no unit to hold.
+fn adjudicate_sanitized(map: &Map, rules: &RuleSet, mstate: &MapState, orders: &mut Orders) -> Result<(HashMap<String, bool>, MapState, HashMap<String, RetreatOptions>), AdjudicationError> {
+    loop {
+        let result = adjudicate(map, rules, mstate, orders).and_then(|order_status| {
+            let (new_mstate, retreats) = apply_adjudication(map, rules, mstate, orders, &order_status)?;
+            Ok((order_status, new_mstate, retreats))
+        });
+
+        match result {
+            Ok(result) => return Ok(result),
+            Err(AdjudicationError::NoUnit(prov)) => { orders.remove(&prov); },
+            Err(AdjudicationError::UnknownProvince(prov)) if !mstate.units.contains_key(&prov) => { orders.remove(&prov); },
+            Err(AdjudicationError::MissingOrder(prov)) | Err(AdjudicationError::UnknownTarget(prov, _)) => { orders.insert(prov, Box::new(Hold)); },
+            Err(err) => return Err(err),
+        }
+    }
+}
+
 /// While this thread runs,
 /// the game at `game_id` should exist.
 async fn game_thread(state: AppState, game_id: String) {
@@ -416,10 +399,26 @@ async fn game_thread(state: AppState, game_id: String) {
          
         let gstate = game.state.as_ref().unwrap();
         if gstate.phase.is_move() {
-            let orders = gstate.current_orders();
-            let order_status = adjudicate(&variant.adj, gstate.current_state(), orders);
-            let (new_mstate, retreats) = apply_adjudication(&variant.adj, gstate.current_state(), orders, &order_status);
+            let gstate = game.state.as_mut().unwrap();
+            let key = (gstate.year, gstate.phase);
+            let orders = gstate.orders.get_mut(&key).unwrap();
+            let (order_status, new_mstate, retreats, error) = match adjudicate_sanitized(&variant.adj, &variant.rules, &gstate.states[&key], orders) {
+                Ok((order_status, new_mstate, retreats)) => (order_status, new_mstate, retreats, None),
+                Err(err) => {
+                    // keep the game going: every unit holds, and the board stays as it is
+                    eprintln!("unable to adjudicate {:?} {} of game {}: {}", key.1, key.0, game_id, err);
+                    let mstate = &gstate.states[&key];
+                    *orders = mstate.units.keys().map(|prov| (prov.to_string(), Box::new(Hold) as Box<dyn Order>)).collect();
+                    let order_status = mstate.units.keys().map(|prov| (prov.to_string(), true)).collect();
+                    (order_status, mstate.clone(), HashMap::new(), Some(err))
+                }
+            };
+
+            if let Some(err) = error {
+                game.broadcast.send(OutMessage::Error { msg: format!("Unable to adjudicate the orders ({}), so all units hold", err) });
+            }
 
+            let gstate = game.state.as_ref().unwrap();
             game.broadcast.send(OutMessage::MovementAdj { 
                 year: gstate.year,
                 phase: gstate.phase,
@@ -439,36 +438,11 @@ async fn game_thread(state: AppState, game_id: String) {
             gstate.states.insert((gstate.year, gstate.phase), new_mstate);
             gstate.orders.insert((gstate.year, gstate.phase), HashMap::new());
         } else if gstate.phase.is_retreat() {
-            let mut new_mstate = gstate.current_state().clone();
             let mvmt_info = gstate.mvmt_info.get(&(gstate.year, gstate.phase.mvmt())).unwrap();
-
-            let mut retreat_units: HashMap<String, Vec<Unit>> = HashMap::new();
-
-            for (prov, order) in gstate.orders.get(&(gstate.year, gstate.phase)).unwrap().iter() {
-                let mov = match order.downcast_ref::<Move>() {
-                    None => continue,
-                    Some(mov) => mov
-                };
-                let retreats = match mvmt_info.retreats.get(prov) {
-                    None => continue,
-                    Some(r) => r
-                };
-                if !retreats.dest.contains(&(mov.dest.0.clone(), mov.dest.1.clone())) {
-                    continue;
-                }
-
-                let vec = retreat_units.entry(mov.dest.0.to_string()).or_default();
-                vec.push(match &retreats.src {
-                    Unit::Army(natl) => Unit::Army(natl.clone()),
-                    Unit::Fleet(natl, _) => Unit::Fleet(natl.clone(), mov.dest.1.clone())
-                });
-            }
-
-            for (prov, units) in retreat_units {
-                if units.len() == 1 {
-                    new_mstate.units.insert(prov, units.into_iter().next().unwrap());
-                } 
-            }
+            let retreat_orders = gstate.current_orders().iter()
+                .map(|(prov, order)| (prov.to_string(), RetreatOrder::from_order(order.deref())))
+                .collect::<HashMap<_, _>>();
+            let (new_mstate, _) = adjudicate_retreats(&variant.adj, gstate.current_state(), &mvmt_info.retreats, &retreat_orders);
 
             let gstate = game.state.as_mut().unwrap();
             let mvmt_info = gstate.mvmt_info.get(&(gstate.year, gstate.phase.mvmt())).unwrap();
@@ -494,47 +468,11 @@ async fn game_thread(state: AppState, game_id: String) {
 
             // update ownership
             if gstate.phase.is_build() {
-                let mstate = gstate.current_state_mut();
-                for (prov, unit) in mstate.units.iter() {
-                    if variant.meta.provinces.get(prov).map(|p| p.is_sc).unwrap_or(false) {
-                        mstate.ownership.insert(prov.to_string(), unit.nationality());
-                    }
-                }
+                update_ownership(&variant.meta, gstate.current_state_mut());
             }
         } else if gstate.phase.is_build() {
-            let mut new_mstate = gstate.current_state().clone();
-            let mut builds = gstate.builds.get(&(gstate.year, gstate.phase.mvmt())).map(|c| c.clone()).unwrap_or_else(HashMap::new);
-
-            for (prov, build) in builds.iter() {
-                let power = match match build {
-                        // safe to use new_mstate since units won't move
-                        Build::Disband => new_mstate.units.get(prov).map(|u| u.nationality()),
-                        _ => new_mstate.ownership.get(prov).map(|s| s.to_string())
-                    } {
-                    None => continue,
-                    Some(power) => power
-                };
-                match build {
-                    Build::Army => new_mstate.units.insert(prov.to_string(), Unit::Army(power.to_string())),
-                    Build::Fleet(coast) => new_mstate.units.insert(prov.to_string(), Unit::Fleet(power.to_string(), coast.to_string())),
-                    Build::Disband => new_mstate.units.remove(prov)
-                };
-            }
-
-            // additional disbands
-            for power in variant.meta.powers.keys() {
-                let supply = count_supply(&new_mstate, &power);
-                let units = count_units(&new_mstate, &power);
-                if units > supply {
-                    for i in 0..(units - supply)  {
-                        let unit: String = disband_cd(&variant.adj, &new_mstate, variant.meta.provinces.iter()
-                        .filter(|(prov, meta)| meta.home_sc == *power)
-                        .map(|(prov, _) | prov.as_str()), &power).unwrap();
-                        new_mstate.units.remove(&unit);
-                        builds.insert(unit, Build::Disband);
-                    }
-                }
-            }
+            let builds = gstate.builds.get(&(gstate.year, gstate.phase.mvmt())).map(|c| c.clone()).unwrap_or_else(HashMap::new);
+            let (new_mstate, builds) = adjudicate_adjustments(&variant.adj, &variant.meta, gstate.current_state(), &builds);
 
             game.broadcast.send(OutMessage::BuildAdj { 
                 year: gstate.year,
@@ -663,61 +601,23 @@ async fn handle_in_message(state: &AppState, game_id: &str, token: &mut String,
                 return Ok(())
             }
 
-            let power = &gstate.players[&*token];
+            let power = gstate.players[&*token].clone();
 
+            let variant = state.variants.get(&variant_id).expect("variant doesn't exist");
             let mstate = gstate.states.get(&(gstate.year, gstate.phase)).unwrap();
-            let supply = count_supply(mstate, power);
-            let units = count_units(mstate, power);
-            if supply < units {
-                let n_disbands = units - supply;
-                if builds.len() > n_disbands {
-                    send(stream, OutMessage::Error { msg: format!("{} disbands, but you only have {}", builds.len(), n_disbands) }).await;
-                    return Ok(())
-                }
-            } else if supply > units {
-                let n_builds = supply - units;
-                if builds.len() > n_builds {
-                    send(stream, OutMessage::Error { msg: format!("{} builds, but you only have {}", builds.len(), n_builds) }).await;
-                    return Ok(())
-                }
+            if let Err(err) = validate_builds(&variant.adj, &variant.meta, mstate, &power, &builds) {
+                send(stream, OutMessage::Error { msg: err.to_string() }).await;
+                return Ok(())
             }
 
             // remove previous builds
-            for prov in mstate.units.iter().filter(|(_, u)| u.nationality() == *power).map(|(p, _)| p.clone()).chain(
-                mstate.ownership.iter().filter(|(p, pwr)| **pwr == *power && !mstate.units.contains_key(*p))
+            for prov in mstate.units.iter().filter(|(_, u)| u.nationality() == power).map(|(p, _)| p.clone()).chain(
+                mstate.ownership.iter().filter(|(p, pwr)| **pwr == power && !mstate.units.contains_key(*p))
                 .map(|(p, _)| p.clone())
-            ) {
+            ).collect::<Vec<_>>() {
                 gstate.builds.entry((gstate.year, gstate.phase)).or_default().remove(&prov);
             }
 
-            let variant = state.variants.get(&variant_id).expect("variant doesn't exist");
-            for (prov, build) in builds.iter() {
-                if gstate.current_state().units.contains_key(prov) && supply > units {
-                    send(stream, OutMessage::Error { msg: format!("Build location {} is occupied", &prov) }).await;
-                    return Ok(())           
-                }
-                if gstate.current_state().ownership.get(prov) != Some(power) && supply > units {
-                    send(stream, OutMessage::Error { msg: format!("Build location {} is not owned by you", &prov) }).await;
-                    return Ok(())
-                }
-                if variant.meta.provinces[prov].home_sc != *power && supply > units {
-                    send(stream, OutMessage::Error { msg: format!("Build location {} is not a home center", &prov) }).await;
-                    return Ok(())
-                }
-
-                match &build {
-                    Build::Disband => if !(supply < units) {
-                        send(stream, OutMessage::Error { msg: format!("No disbands; can't disband {}", &prov) }).await;
-                    },
-                    Build::Army => if !(supply > units) {
-                        send(stream, OutMessage::Error { msg: format!("No builds; can't build A {}", &prov) }).await;
-                    },
-                    Build::Fleet(_) => if !(supply > units) {
-                        send(stream, OutMessage::Error { msg: format!("No builds; can't build F {}", &prov) }).await;
-                    },
-                }
-            }
-
             gstate.builds.entry((gstate.year, gstate.phase)).or_default().extend(builds);
         },
         InMessage::Orders { orders } => {
@@ -731,6 +631,8 @@ async fn handle_in_message(state: &AppState, game_id: &str, token: &mut String,
                 return Ok(())
             }
 
+            let variant_id = game.meta.variant.clone();
+
             let gstate = game.state.as_mut().unwrap();
             if gstate.phase.is_build() {
                 send(stream, OutMessage::Error { msg: "Not a movement or retreat phase".to_string() }).await;
@@ -745,14 +647,20 @@ async fn handle_in_message(state: &AppState, game_id: &str, token: &mut String,
             // authenticate 
             if gstate.phase.is_move() {
                 let power = gstate.players[token].as_str();
+                let variant = state.variants.get(&variant_id).expect("variant doesn't exist");
                 for (prov, order) in orders.iter() {
                     if gstate.current_state().units.get(prov).map(|u| u.nationality()).unwrap_or("".to_string()) != power {       
                         send(stream, OutMessage::Error { msg: format!("Invalid orderset: you do not have a unit at {}", prov) }).await;
                         return Ok(())
                     }
+                    if let Err(err) = validate_order(&variant.compiled, &variant.rules, gstate.current_state(), prov, order.deref()) {
+                        send(stream, OutMessage::Error { msg: format!("Invalid order for {}: {}", prov, err) }).await;
+                        return Ok(())
+                    }
                 }
             } else {
                 let power = gstate.players[token].as_str();
+                let variant = state.variants.get(&variant_id).expect("variant doesn't exist");
                 for (prov, order) in orders.iter() {                    
                     let info = match  gstate.mvmt_info.get(&(gstate.year, gstate.phase.mvmt())) {
                         Some(info) => info,
@@ -779,7 +687,7 @@ async fn handle_in_message(state: &AppState, game_id: &str, token: &mut String,
                         }
                     };
 
-                    if info.retreats.get(prov).is_none() || !info.retreats[prov].dest.contains(&mov.dest) {
+                    if info.retreats.get(prov).and_then(|options| retreat_dest(&variant.adj, options, &mov.dest)).is_none() {
                         send(stream, OutMessage::Error { msg: format!("Cannot retreat {} to {}", prov, &mov.dest.0) }).await;
                         return Ok(())    
                     }
@@ -6,7 +6,7 @@ use dashmap::DashMap;
 use games::{Game, PosData};
 use google_auth_verifier::auth::{AuthVerifierClient, AuthenticationError};
 
-use radip::{utils::{MapMeta, PowerMeta, ProvinceMeta}, Map, MapState, ProvinceAbbr, Unit};
+use radip::{utils::{MapMeta, PowerMeta, ProvinceMeta}, CompiledMap, Map, MapState, ProvinceAbbr, RuleSet, Unit};
 use rocket::{form::Form, fs::{FileServer, TempFile}, http::{ContentType, Cookie, CookieJar, Status}, response::{content::RawHtml, Redirect, Responder}, serde::json::Json, tokio::io::AsyncReadExt, State};
 use nanoid::nanoid;
 
@@ -25,9 +25,12 @@ struct AppState {
 
 struct Variant {
     pub adj: Map,
+    /// `adj` compiled once, for validating and listing orders.
+    pub compiled: CompiledMap,
     pub svg: String,
     pub meta: MapMeta,
     pub pos: PosData,
+    pub rules: RuleSet,
 }
 
 struct UserMeta {
@@ -179,6 +182,7 @@ async fn rocket() -> shuttle_rocket::ShuttleRocket {
         games::create_game_submit,
         games::game,
         games::game_meta,
+        games::game_legal_orders,
         games::game_stream,
 
         variant_adj, variant_svg, variant_pos, variant_meta,